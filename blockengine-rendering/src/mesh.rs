@@ -21,6 +21,10 @@ use {
 pub struct Mesh {
     pub vbuf: Buffer<Resources, Vertex>,
     pub slice: Slice<Resources>,
+    /// The minimum corner of the box containing all vertices.
+    pub min: Point3<f32>,
+    /// The maximum corner of the box containing all vertices.
+    pub max: Point3<f32>,
 }
 
 impl Mesh {
@@ -28,9 +32,19 @@ impl Mesh {
         let factory = graphics::get_factory(ctx);
         let (vbuf, slice) = factory.create_vertex_buffer_with_slice(vertices, ());
 
+        // Used to skip drawing meshes that aren't on screen
+        let position = |vertex: &Vertex| Point3::new(vertex.pos[0], vertex.pos[1], vertex.pos[2]);
+        let first = vertices.first().map(&position).unwrap_or(Point3::new(0.0, 0.0, 0.0));
+        let (min, max) = vertices.iter().map(position).fold((first, first), |(min, max), p| (
+            Point3::new(min.x.min(p.x), min.y.min(p.y), min.z.min(p.z)),
+            Point3::new(max.x.max(p.x), max.y.max(p.y), max.z.max(p.z)),
+        ));
+
         Rc::new(Self {
             vbuf,
             slice,
+            min,
+            max,
         })
     }
 
@@ -44,8 +58,8 @@ impl Mesh {
 pub fn triangulate_voxels(voxels: &Voxels<bool>) -> Vec<Vertex> {
     // Add some cubes
     let mut vertices = Vec::new();
-    for position in voxels.iter_pos() {
-        if *voxels.get(position).unwrap() {
+    for (position, solid) in voxels.enumerate() {
+        if *solid {
             add_cube_vertices(
                 &mut vertices,
                 Vector3::new(position.x as f32, position.y as f32, position.z as f32)
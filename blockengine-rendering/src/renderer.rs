@@ -12,7 +12,7 @@ use {
     gfx_device_gl::{Resources},
     cgmath::{EuclideanSpace, Matrix4},
 
-    lagato::{camera::{RenderCamera}},
+    lagato::{camera::{RenderCamera, Containment}},
 
     Object,
 };
@@ -81,6 +81,7 @@ impl Renderer {
             encoder.clear(&color_view, [0.1, 0.1, 0.1, 1.0]);
             encoder.clear_depth(&depth_view, 1.0);
 
+            let frustum = camera.frustum();
             let camera = camera.model_view_matrix();
 
             for object in objects {
@@ -88,7 +89,15 @@ impl Renderer {
                     continue
                 }
 
-                let model = Matrix4::from_translation(object.position.to_vec());
+                // Skip objects that aren't on screen
+                let offset = object.position.to_vec();
+                let min = object.mesh.min + offset;
+                let max = object.mesh.max + offset;
+                if frustum.test_aabb(min, max) == Containment::Outside {
+                    continue
+                }
+
+                let model = Matrix4::from_translation(offset);
                 let transform = camera * model;
                 let locals = Locals {
                     transform: transform.into(),
@@ -0,0 +1,66 @@
+#[macro_use]
+extern crate criterion;
+extern crate cgmath;
+extern crate lagato;
+
+use {
+    criterion::{Criterion, black_box},
+    cgmath::{Point3, Vector3},
+
+    lagato::grid::{Dim, Grid, Connectivity},
+};
+
+const SIZE: i32 = 64;
+
+fn filled<D: Dim<Vector=Vector3<i32>, Point=Point3<i32>>>() -> Grid<u32, D> {
+    let mut grid: Grid<u32, D> = Grid::empty(Vector3::new(SIZE, SIZE, SIZE));
+    for (position, cell) in grid.enumerate_mut() {
+        *cell = (position.x ^ position.y ^ position.z) as u32;
+    }
+    grid
+}
+
+/// Sums every cell with its 6 direct neighbors, like a blur or an automaton step would.
+fn neighbor_sum<D: Dim<Vector=Vector3<i32>, Point=Point3<i32>>>(grid: &Grid<u32, D>) -> u32 {
+    let offsets = D::neighbor_offsets(Connectivity::Orthogonal);
+    let mut total = 0u32;
+
+    for (position, cell) in grid.enumerate() {
+        total = total.wrapping_add(*cell);
+        for offset in &offsets {
+            if let Ok(neighbor) = grid.get(position + offset) {
+                total = total.wrapping_add(*neighbor);
+            }
+        }
+    }
+
+    total
+}
+
+/// Walks every column along Z, the worst case for row by row layouts.
+fn column_walk<D: Dim<Vector=Vector3<i32>, Point=Point3<i32>>>(grid: &Grid<u32, D>) -> u32 {
+    let mut total = 0u32;
+
+    for x in 0..SIZE {
+        for y in 0..SIZE {
+            for z in 0..SIZE {
+                total = total.wrapping_add(*grid.get(Point3::new(x, y, z)).unwrap());
+            }
+        }
+    }
+
+    total
+}
+
+fn layouts(c: &mut Criterion) {
+    let row: Grid<u32, lagato::grid::Dim3> = filled();
+    let morton: Grid<u32, lagato::grid::Dim3Morton> = filled();
+
+    c.bench_function("neighbor sum row by row", |b| b.iter(|| neighbor_sum(black_box(&row))));
+    c.bench_function("neighbor sum morton", |b| b.iter(|| neighbor_sum(black_box(&morton))));
+    c.bench_function("column walk row by row", |b| b.iter(|| column_walk(black_box(&row))));
+    c.bench_function("column walk morton", |b| b.iter(|| column_walk(black_box(&morton))));
+}
+
+criterion_group!(benches, layouts);
+criterion_main!(benches);
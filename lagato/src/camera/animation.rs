@@ -0,0 +1,279 @@
+use {
+    cgmath::{
+        EuclideanSpace, InnerSpace, Point2, Point3, Vector3, Quaternion, Euler, Rad, Zero,
+    },
+
+    camera::{RenderCamera},
+    noise::{Perlin},
+};
+
+/// Where a camera should be at a point in time.
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    /// In seconds from the start of the path.
+    pub time: f32,
+    pub position: Point3<f32>,
+    pub rotation: Quaternion<f32>,
+    /// Where the Bezier control points are relative to the position, the path arrives from
+    /// `position - tangent` and leaves towards `position + tangent`. Only used for
+    /// `Interpolation::Bezier`.
+    pub tangent: Vector3<f32>,
+}
+
+impl Keyframe {
+    pub fn new(time: f32, position: Point3<f32>, rotation: Quaternion<f32>) -> Self {
+        Keyframe {
+            time,
+            position,
+            rotation,
+            tangent: Vector3::zero(),
+        }
+    }
+}
+
+/// How positions are interpolated between keyframes. Rotations are always interpolated
+/// along the shortest arc.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Interpolation {
+    /// Straight lines between keyframes.
+    Linear,
+    /// A smooth curve through all keyframes, shaped by the keyframes before and after.
+    CatmullRom,
+    /// Curves shaped by every keyframe's tangent. Zero tangents ease in and out of every
+    /// keyframe.
+    Bezier,
+}
+
+/// A path for animating a camera through keyframes, such as in cutscenes.
+pub struct CameraPath {
+    pub interpolation: Interpolation,
+    keyframes: Vec<Keyframe>,
+}
+
+impl CameraPath {
+    pub fn new(interpolation: Interpolation) -> Self {
+        CameraPath {
+            interpolation,
+            keyframes: Vec::new(),
+        }
+    }
+
+    /// The keyframes, ordered by time.
+    pub fn keyframes(&self) -> &[Keyframe] {
+        &self.keyframes
+    }
+
+    /// Adds a keyframe, keeping the keyframes ordered by time.
+    pub fn add(&mut self, keyframe: Keyframe) {
+        let index = self.keyframes.iter().position(|k| k.time > keyframe.time)
+            .unwrap_or(self.keyframes.len());
+        self.keyframes.insert(index, keyframe);
+    }
+
+    /// The time of the last keyframe.
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map(|k| k.time).unwrap_or(0.0)
+    }
+
+    /// The position and rotation at a time, staying at the first or last keyframe before or
+    /// after the path. Returns `None` if there are no keyframes.
+    pub fn sample(&self, time: f32) -> Option<(Point3<f32>, Quaternion<f32>)> {
+        let keyframes = &self.keyframes;
+        let next = keyframes.iter().position(|k| k.time > time).unwrap_or(keyframes.len());
+        if next == 0 || next == keyframes.len() {
+            let keyframe = if next == 0 { keyframes.first()? } else { keyframes.last()? };
+            return Some((keyframe.position, keyframe.rotation))
+        }
+
+        let (a, b) = (&keyframes[next - 1], &keyframes[next]);
+        let t = (time - a.time) / (b.time - a.time);
+
+        let position = match self.interpolation {
+            Interpolation::Linear => a.position + (b.position - a.position) * t,
+            Interpolation::CatmullRom => {
+                // The path continues straight on at the ends
+                let before = if next >= 2 {
+                    keyframes[next - 2].position
+                } else {
+                    a.position + (a.position - b.position)
+                };
+                let after = keyframes.get(next + 1).map(|k| k.position)
+                    .unwrap_or(b.position + (b.position - a.position));
+                catmull_rom(before, a.position, b.position, after, t)
+            },
+            Interpolation::Bezier =>
+                bezier(a.position, a.position + a.tangent, b.position - b.tangent, b.position, t),
+        };
+
+        // Quaternions q and -q are the same rotation, pick the one closest to a so slerp
+        // doesn't take the long way around
+        let target = if a.rotation.dot(b.rotation) < 0.0 { -b.rotation } else { b.rotation };
+        let rotation = a.rotation.slerp(target, t).normalize();
+
+        Some((position, rotation))
+    }
+
+    /// Moves a camera to where it should be at a time.
+    pub fn apply(&self, camera: &mut RenderCamera, time: f32) {
+        if let Some((position, rotation)) = self.sample(time) {
+            camera.position = position;
+            camera.rotation = rotation;
+        }
+    }
+}
+
+/// Shakes a camera based on trauma, which is added by things like explosions and slowly wears
+/// off. The shake grows with the square of the trauma, so small hits are subtle and big hits
+/// stand out.
+pub struct CameraShake {
+    /// From 0 to 1.
+    pub trauma: f32,
+    /// How much trauma wears off per second.
+    pub decay: f32,
+    /// How far the camera turns around every axis at full trauma.
+    pub max_angle: Rad<f32>,
+    /// How far the camera moves along every axis at full trauma.
+    pub max_offset: f32,
+    /// How quickly the shake changes direction.
+    pub frequency: f32,
+    time: f32,
+    noise: Perlin,
+}
+
+impl CameraShake {
+    pub fn new(seed: u64) -> Self {
+        CameraShake {
+            trauma: 0.0,
+            decay: 1.0,
+            max_angle: Rad(0.1),
+            max_offset: 0.0,
+            frequency: 15.0,
+            time: 0.0,
+            noise: Perlin::new(seed),
+        }
+    }
+
+    /// Adds trauma, up to a maximum of 1.
+    pub fn add_trauma(&mut self, amount: f32) {
+        self.trauma = (self.trauma + amount).clamp(0.0, 1.0);
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        self.time += dt;
+        self.trauma = (self.trauma - self.decay * dt).max(0.0);
+    }
+
+    /// Shakes a camera, this should be applied to the camera every frame after positioning it.
+    pub fn apply(&self, camera: &mut RenderCamera) {
+        if self.trauma <= 0.0 {
+            return
+        }
+
+        let shake = self.trauma * self.trauma;
+        let t = self.time * self.frequency;
+        // Every value gets its own row of noise, offset so it's never sampled at integers
+        let sample = |row: u32| self.noise.get2(Point2::new(t, row as f32 * 10.0 + 0.5)) * shake;
+
+        let rotation = Quaternion::from(Euler {
+            x: self.max_angle * sample(0),
+            y: self.max_angle * sample(1),
+            z: self.max_angle * sample(2),
+        });
+        let offset = Vector3::new(sample(3), sample(4), sample(5)) * self.max_offset;
+
+        camera.position += camera.rotation * offset;
+        camera.rotation = (camera.rotation * rotation).normalize();
+    }
+}
+
+fn catmull_rom(
+    p0: Point3<f32>, p1: Point3<f32>, p2: Point3<f32>, p3: Point3<f32>, t: f32,
+) -> Point3<f32> {
+    let (p0, p1, p2, p3) = (p0.to_vec(), p1.to_vec(), p2.to_vec(), p3.to_vec());
+    let (t2, t3) = (t * t, t * t * t);
+
+    Point3::from_vec((
+        p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3
+    ) * 0.5)
+}
+
+fn bezier(
+    p0: Point3<f32>, p1: Point3<f32>, p2: Point3<f32>, p3: Point3<f32>, t: f32,
+) -> Point3<f32> {
+    let u = 1.0 - t;
+    Point3::from_vec(
+        p0.to_vec() * (u * u * u)
+        + p1.to_vec() * (3.0 * u * u * t)
+        + p2.to_vec() * (3.0 * u * t * t)
+        + p3.to_vec() * (t * t * t)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{InnerSpace, Rotation3, Point3, Vector2, Quaternion, Rad, One},
+
+        camera::{RenderCamera, CameraPath, CameraShake, Keyframe, Interpolation},
+    };
+
+    fn path(interpolation: Interpolation) -> CameraPath {
+        let mut path = CameraPath::new(interpolation);
+        path.add(Keyframe::new(
+            2.0, Point3::new(10.0, 0.0, 0.0), Quaternion::from_angle_y(Rad(1.0)),
+        ));
+        path.add(Keyframe::new(0.0, Point3::new(0.0, 0.0, 0.0), Quaternion::one()));
+        path.add(Keyframe::new(4.0, Point3::new(20.0, 0.0, 0.0), Quaternion::one()));
+        path
+    }
+
+    #[test]
+    fn paths_interpolate_between_keyframes() {
+        for interpolation in &[Interpolation::Linear, Interpolation::CatmullRom] {
+            let path = path(*interpolation);
+            assert_eq!(path.duration(), 4.0);
+
+            let (position, rotation) = path.sample(1.0).unwrap();
+            assert!((position - Point3::new(5.0, 0.0, 0.0)).magnitude() < 1e-5);
+            let expected = Quaternion::from_angle_y(Rad(0.5));
+            assert!((rotation - expected).magnitude() < 1e-5);
+
+            assert_eq!(path.sample(-1.0).unwrap().0, Point3::new(0.0, 0.0, 0.0));
+            assert_eq!(path.sample(9.0).unwrap().0, Point3::new(20.0, 0.0, 0.0));
+        }
+
+        // Zero tangents ease in, so the camera hasn't gotten as far yet early on
+        let bezier = path(Interpolation::Bezier);
+        assert!(bezier.sample(0.5).unwrap().0.x < 2.5);
+        assert!((bezier.sample(1.0).unwrap().0.x - 5.0).abs() < 1e-5);
+        assert!(CameraPath::new(Interpolation::Linear).sample(0.0).is_none());
+    }
+
+    #[test]
+    fn shake_wears_off() {
+        let mut camera = RenderCamera::new(
+            Point3::new(0.0, 0.0, 0.0), Quaternion::one(), Vector2::new(100, 100),
+        );
+        let mut shake = CameraShake::new(3);
+        shake.max_offset = 1.0;
+
+        shake.update(0.1);
+        shake.apply(&mut camera);
+        assert_eq!(camera.rotation, Quaternion::one());
+
+        shake.add_trauma(2.0);
+        assert_eq!(shake.trauma, 1.0);
+        shake.update(0.123);
+        shake.apply(&mut camera);
+        assert!(camera.rotation != Quaternion::one());
+        assert!(camera.position != Point3::new(0.0, 0.0, 0.0));
+        assert!((camera.position - Point3::new(0.0, 0.0, 0.0)).magnitude() < 3.0f32.sqrt());
+        assert!(camera.rotation.s > (0.15f32).cos());
+
+        shake.update(2.0);
+        assert_eq!(shake.trauma, 0.0);
+    }
+}
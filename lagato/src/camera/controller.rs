@@ -0,0 +1,351 @@
+use {
+    cgmath::{InnerSpace, Rotation3, Point3, Vector2, Vector3, Quaternion, Rad, Zero},
+
+    camera::{PitchYawCamera, OrbitingCamera, RenderCamera},
+    DirectionalInput,
+};
+
+/// How mouse movement in pixels turns into camera rotation.
+#[derive(Clone, Copy, Debug)]
+pub struct LookSettings {
+    /// Radians turned per pixel of mouse movement.
+    pub sensitivity: f32,
+    pub invert_x: bool,
+    pub invert_y: bool,
+    /// How far the camera can look up or down from level.
+    pub pitch_limit: Rad<f32>,
+    /// Roughly how many seconds the camera takes to catch up with the input, 0 disables
+    /// smoothing.
+    pub smoothing: f32,
+}
+
+impl LookSettings {
+    /// The change in yaw and pitch for a mouse movement.
+    pub fn apply(&self, relative: Vector2<f32>) -> (Rad<f32>, Rad<f32>) {
+        let invert = |inverted: bool| if inverted { -1.0 } else { 1.0 };
+        (
+            Rad(relative.x * -self.sensitivity * invert(self.invert_x)),
+            Rad(relative.y * -self.sensitivity * invert(self.invert_y)),
+        )
+    }
+
+    pub fn clamp_pitch(&self, pitch: Rad<f32>) -> Rad<f32> {
+        Rad(pitch.0.max(-self.pitch_limit.0).min(self.pitch_limit.0))
+    }
+
+    /// How far to move towards the target this frame.
+    fn smoothing_factor(&self, dt: f32) -> f32 {
+        if self.smoothing <= 0.0 { 1.0 } else { 1.0 - (-dt / self.smoothing).exp() }
+    }
+}
+
+impl Default for LookSettings {
+    fn default() -> Self {
+        LookSettings {
+            sensitivity: 0.0025,
+            invert_x: false,
+            invert_y: false,
+            pitch_limit: Rad(::std::f32::consts::PI * 0.475),
+            smoothing: 0.0,
+        }
+    }
+}
+
+/// A noclip camera flying in whichever direction it's looking, speeding up and slowing down
+/// gradually.
+pub struct FlyController {
+    pub position: Point3<f32>,
+    pub velocity: Vector3<f32>,
+    /// The rotation the camera is currently at, which lags behind the input when smoothing.
+    /// Use `set_camera` to change it, changes made directly are undone by the next `update`.
+    pub camera: PitchYawCamera,
+    pub look: LookSettings,
+    /// Units per second at full speed.
+    pub speed: f32,
+    /// Units per second per second when speeding up or slowing down.
+    pub acceleration: f32,
+    target: PitchYawCamera,
+}
+
+impl FlyController {
+    pub fn new(position: Point3<f32>, camera: PitchYawCamera) -> Self {
+        FlyController {
+            position,
+            velocity: Vector3::zero(),
+            camera,
+            look: LookSettings::default(),
+            speed: 10.0,
+            acceleration: 40.0,
+            target: camera,
+        }
+    }
+
+    /// Turns the camera to a new rotation right away, without smoothing.
+    pub fn set_camera(&mut self, camera: PitchYawCamera) {
+        self.camera = camera;
+        self.target = camera;
+    }
+
+    pub fn handle_mouse_motion(&mut self, relative: Vector2<i32>) {
+        self.target.look(relative.cast().unwrap(), &self.look);
+    }
+
+    /// Moves the camera, with `vertical` going from -1 for down to 1 for up.
+    pub fn update(&mut self, input: &DirectionalInput, vertical: f32, dt: f32) {
+        smooth_look(&mut self.camera, &self.target, &self.look, dt);
+
+        let direction = self.camera.to_rotation() * input_vector(input, vertical);
+        self.velocity = accelerate(self.velocity, direction * self.speed, self.acceleration * dt);
+        self.position += self.velocity * dt;
+    }
+
+    pub fn to_render_camera(&self, window_size: Vector2<u32>) -> RenderCamera {
+        self.camera.to_render_camera(self.position, window_size)
+    }
+}
+
+/// A first-person camera walking along the ground, only looking up and down changes the
+/// view but not the direction it walks in. Vertical movement, such as gravity and jumping, is
+/// left to the game's physics through `velocity`.
+pub struct WalkController {
+    /// The position of the camera, at eye height.
+    pub position: Point3<f32>,
+    pub velocity: Vector3<f32>,
+    /// The rotation the camera is currently at, which lags behind the input when smoothing.
+    /// Use `set_camera` to change it, changes made directly are undone by the next `update`.
+    pub camera: PitchYawCamera,
+    pub look: LookSettings,
+    /// Units per second at full speed.
+    pub speed: f32,
+    /// Units per second per second when speeding up or slowing down.
+    pub acceleration: f32,
+    target: PitchYawCamera,
+}
+
+impl WalkController {
+    pub fn new(position: Point3<f32>, camera: PitchYawCamera) -> Self {
+        WalkController {
+            position,
+            velocity: Vector3::zero(),
+            camera,
+            look: LookSettings::default(),
+            speed: 4.0,
+            acceleration: 30.0,
+            target: camera,
+        }
+    }
+
+    /// Turns the camera to a new rotation right away, without smoothing.
+    pub fn set_camera(&mut self, camera: PitchYawCamera) {
+        self.camera = camera;
+        self.target = camera;
+    }
+
+    pub fn handle_mouse_motion(&mut self, relative: Vector2<i32>) {
+        self.target.look(relative.cast().unwrap(), &self.look);
+    }
+
+    pub fn update(&mut self, input: &DirectionalInput, dt: f32) {
+        smooth_look(&mut self.camera, &self.target, &self.look, dt);
+
+        // Only accelerate horizontally, vertical velocity is kept as it is
+        let direction = Quaternion::from_angle_y(self.camera.yaw) * input_vector(input, 0.0);
+        let horizontal = Vector3::new(self.velocity.x, 0.0, self.velocity.z);
+        let horizontal = accelerate(horizontal, direction * self.speed, self.acceleration * dt);
+        self.velocity = Vector3::new(horizontal.x, self.velocity.y, horizontal.z);
+
+        self.position += self.velocity * dt;
+    }
+
+    pub fn to_render_camera(&self, window_size: Vector2<u32>) -> RenderCamera {
+        self.camera.to_render_camera(self.position, window_size)
+    }
+}
+
+/// Orbits an `OrbitingCamera` around its focus, with zooming and panning.
+pub struct OrbitController {
+    /// The camera as it currently is, which lags behind the input when smoothing. Use
+    /// `set_camera` to change it, changes made directly are undone by the next `update`.
+    pub camera: OrbitingCamera,
+    pub look: LookSettings,
+    /// How much every step of zooming in changes the distance, from 0 to 1.
+    pub zoom_speed: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// How far the focus moves per pixel of mouse movement, relative to the distance.
+    pub pan_sensitivity: f32,
+    target: OrbitingCamera,
+}
+
+impl OrbitController {
+    pub fn new(camera: OrbitingCamera) -> Self {
+        OrbitController {
+            camera,
+            look: LookSettings::default(),
+            zoom_speed: 0.1,
+            min_distance: 1.0,
+            max_distance: 1000.0,
+            pan_sensitivity: 0.002,
+            target: camera,
+        }
+    }
+
+    /// Moves the camera to a new focus, rotation and distance right away, without smoothing.
+    pub fn set_camera(&mut self, camera: OrbitingCamera) {
+        self.camera = camera;
+        self.target = camera;
+    }
+
+    pub fn rotate(&mut self, relative: Vector2<i32>) {
+        let (yaw, pitch) = self.look.apply(relative.cast().unwrap());
+        self.target.yaw += yaw;
+        self.target.pitch = self.look.clamp_pitch(self.target.pitch + pitch);
+    }
+
+    /// Zooms in by a number of steps, such as scroll wheel clicks. Negative steps zoom out.
+    pub fn zoom(&mut self, steps: f32) {
+        let distance = self.target.distance * (1.0 - self.zoom_speed).powf(steps);
+        self.target.distance = distance.max(self.min_distance).min(self.max_distance);
+    }
+
+    /// Moves the focus along the screen, so it follows the mouse.
+    pub fn pan(&mut self, relative: Vector2<i32>) {
+        let (_, rotation) = self.target.to_position_rotation();
+        let scale = self.target.distance * self.pan_sensitivity;
+        let offset = rotation * Vector3::new(-relative.x as f32, relative.y as f32, 0.0);
+        self.target.focus += offset * scale;
+    }
+
+    pub fn update(&mut self, dt: f32) {
+        let factor = self.look.smoothing_factor(dt);
+        let camera = &mut self.camera;
+        let target = &self.target;
+
+        camera.focus += (target.focus - camera.focus) * factor;
+        camera.pitch += (target.pitch - camera.pitch) * factor;
+        camera.yaw += (target.yaw - camera.yaw) * factor;
+        camera.distance += (target.distance - camera.distance) * factor;
+    }
+
+    pub fn to_render_camera(&self, window_size: Vector2<u32>) -> RenderCamera {
+        self.camera.to_render_camera(window_size)
+    }
+}
+
+fn smooth_look(
+    camera: &mut PitchYawCamera, target: &PitchYawCamera, look: &LookSettings, dt: f32,
+) {
+    let factor = look.smoothing_factor(dt);
+    camera.pitch += (target.pitch - camera.pitch) * factor;
+    camera.yaw += (target.yaw - camera.yaw) * factor;
+}
+
+/// The direction to move in relative to the camera, forward being -Z.
+fn input_vector(input: &DirectionalInput, vertical: f32) -> Vector3<f32> {
+    let input = input.to_vector();
+    let direction = Vector3::new(input.x, vertical, input.y);
+    if direction.magnitude2() > 1.0 { direction.normalize() } else { direction }
+}
+
+/// Changes the velocity towards the target velocity, by at most `max_change`.
+fn accelerate(velocity: Vector3<f32>, target: Vector3<f32>, max_change: f32) -> Vector3<f32> {
+    let difference = target - velocity;
+    let distance = difference.magnitude();
+    if distance <= max_change {
+        target
+    } else {
+        velocity + difference * (max_change / distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{InnerSpace, Point3, Vector2, Vector3, Rad},
+
+        camera::{PitchYawCamera, OrbitingCamera, LookSettings, FlyController, OrbitController},
+        DirectionalInput,
+    };
+
+    #[test]
+    fn look_settings_invert_and_limit() {
+        let settings = LookSettings {
+            invert_y: true,
+            pitch_limit: Rad(0.5),
+            .. LookSettings::default()
+        };
+
+        let mut camera = PitchYawCamera::new(Rad(0.0), Rad(0.0));
+        camera.look(Vector2::new(100.0, 100.0), &settings);
+        assert_eq!(camera.yaw, Rad(-0.25));
+        assert_eq!(camera.pitch, Rad(0.25));
+
+        camera.look(Vector2::new(0.0, 1000.0), &settings);
+        assert_eq!(camera.pitch, Rad(0.5));
+    }
+
+    #[test]
+    fn fly_controller_accelerates_and_smooths() {
+        let mut controller = FlyController::new(
+            Point3::new(0.0, 0.0, 0.0), PitchYawCamera::new(Rad(0.0), Rad(0.0)),
+        );
+        let mut input = DirectionalInput::new();
+        input.forward = true;
+
+        controller.update(&input, 0.0, 0.1);
+        assert!((controller.velocity - Vector3::new(0.0, 0.0, -4.0)).magnitude2() < 1e-6);
+        for _ in 0..10 {
+            controller.update(&input, 0.0, 0.1);
+        }
+        assert!((controller.velocity - Vector3::new(0.0, 0.0, -10.0)).magnitude2() < 1e-6);
+        assert!(controller.position.z < -5.0);
+
+        controller.look.smoothing = 0.1;
+        controller.handle_mouse_motion(Vector2::new(-400, 0));
+        controller.update(&DirectionalInput::new(), 0.0, 0.1);
+        assert!(controller.camera.yaw.0 > 0.5 && controller.camera.yaw.0 < 1.0);
+        for _ in 0..50 {
+            controller.update(&DirectionalInput::new(), 0.0, 0.1);
+        }
+        assert!((controller.camera.yaw.0 - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn orbit_controller_zooms_and_pans() {
+        let camera = OrbitingCamera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad(0.0), 10.0);
+        let mut controller = OrbitController::new(camera);
+        controller.min_distance = 5.0;
+
+        controller.zoom(1.0);
+        controller.update(1.0);
+        assert!((controller.camera.distance - 9.0).abs() < 1e-5);
+        controller.zoom(100.0);
+        controller.update(1.0);
+        assert_eq!(controller.camera.distance, 5.0);
+
+        controller.pan(Vector2::new(-100, 0));
+        controller.update(1.0);
+        assert!(controller.camera.focus.x > 0.0);
+        assert!(controller.camera.focus.y.abs() < 1e-6 && controller.camera.focus.z.abs() < 1e-6);
+    }
+
+    #[test]
+    fn set_cameras_survive_smoothing() {
+        let mut fly = FlyController::new(
+            Point3::new(0.0, 0.0, 0.0), PitchYawCamera::new(Rad(0.0), Rad(0.0)),
+        );
+        fly.look.smoothing = 0.5;
+        fly.set_camera(PitchYawCamera::new(Rad(0.3), Rad(2.0)));
+        fly.update(&DirectionalInput::new(), 0.0, 0.1);
+        assert_eq!(fly.camera.yaw, Rad(2.0));
+        assert_eq!(fly.camera.pitch, Rad(0.3));
+
+        let camera = OrbitingCamera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad(0.0), 10.0);
+        let mut orbit = OrbitController::new(camera);
+        orbit.look.smoothing = 0.5;
+        orbit.set_camera(OrbitingCamera::new(Point3::new(5.0, 1.0, 2.0), Rad(0.2), Rad(1.0), 3.0));
+        orbit.update(0.1);
+        assert_eq!(orbit.camera.focus, Point3::new(5.0, 1.0, 2.0));
+        assert_eq!(orbit.camera.distance, 3.0);
+    }
+}
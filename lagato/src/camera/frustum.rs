@@ -0,0 +1,200 @@
+use {
+    cgmath::{InnerSpace, Point3, Vector3, Vector4, Matrix4},
+
+    grid::{Dim3, Range},
+};
+
+/// Where a shape is relative to a frustum.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Containment {
+    Inside,
+    /// Partially inside, or close enough to a corner of the frustum that it can't be told
+    /// apart cheaply. These shapes should be treated as visible.
+    Intersecting,
+    Outside,
+}
+
+/// The volume of space a camera can see, for skipping things that aren't on screen.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    /// The left, right, bottom, top, near and far planes, with normals pointing inwards. A
+    /// point is in front of a plane if `normal.dot(point) + w` is positive.
+    planes: [Vector4<f32>; 6],
+}
+
+impl Frustum {
+    /// Extracts the frustum from a combined projection and view matrix, such as
+    /// `RenderCamera::model_view_matrix`.
+    pub fn from_matrix(matrix: Matrix4<f32>) -> Self {
+        let row = |i: usize| Vector4::new(matrix.x[i], matrix.y[i], matrix.z[i], matrix.w[i]);
+        let normalize = |plane: Vector4<f32>| plane / plane.truncate().magnitude();
+
+        Frustum {
+            planes: [
+                normalize(row(3) + row(0)),
+                normalize(row(3) - row(0)),
+                normalize(row(3) + row(1)),
+                normalize(row(3) - row(1)),
+                normalize(row(3) + row(2)),
+                normalize(row(3) - row(2)),
+            ],
+        }
+    }
+
+    pub fn test_point(&self, point: Point3<f32>) -> Containment {
+        self.test_sphere(point, 0.0)
+    }
+
+    pub fn test_sphere(&self, center: Point3<f32>, radius: f32) -> Containment {
+        let mut containment = Containment::Inside;
+
+        for plane in &self.planes {
+            let distance = distance(*plane, center);
+            if distance < -radius {
+                return Containment::Outside
+            }
+            if distance < radius {
+                containment = Containment::Intersecting;
+            }
+        }
+
+        containment
+    }
+
+    /// Tests an axis-aligned box, from its minimum to its maximum corner.
+    pub fn test_aabb(&self, min: Point3<f32>, max: Point3<f32>) -> Containment {
+        let mut containment = Containment::Inside;
+
+        for plane in &self.planes {
+            // Only the corners furthest along and furthest against the plane's normal matter
+            let pick = |positive: bool| Point3::new(
+                if (plane.x >= 0.0) == positive { max.x } else { min.x },
+                if (plane.y >= 0.0) == positive { max.y } else { min.y },
+                if (plane.z >= 0.0) == positive { max.z } else { min.z },
+            );
+
+            if distance(*plane, pick(true)) < 0.0 {
+                return Containment::Outside
+            }
+            if distance(*plane, pick(false)) < 0.0 {
+                containment = Containment::Intersecting;
+            }
+        }
+
+        containment
+    }
+
+    /// Finds which chunks in a range of chunk coordinates aren't outside the frustum. Large
+    /// blocks of chunks are tested at once, so only the chunks along the edges of the frustum
+    /// are tested one by one.
+    pub fn cull_chunks(&self, chunks: Range<Dim3>, chunk_size: Vector3<f32>) -> Vec<Point3<i32>> {
+        let mut visible = Vec::new();
+        if !chunks.is_empty() {
+            self.cull_chunks_recursive(chunks, chunk_size, &mut visible);
+        }
+        visible
+    }
+
+    fn cull_chunks_recursive(
+        &self, chunks: Range<Dim3>, chunk_size: Vector3<f32>, visible: &mut Vec<Point3<i32>>,
+    ) {
+        let corner = |chunk: Point3<i32>| Point3::new(
+            chunk.x as f32 * chunk_size.x,
+            chunk.y as f32 * chunk_size.y,
+            chunk.z as f32 * chunk_size.z,
+        );
+        let min = corner(chunks.start);
+        let max = corner(chunks.end + Vector3::new(1, 1, 1));
+
+        match self.test_aabb(min, max) {
+            Containment::Outside => {},
+            Containment::Inside => visible.extend(chunks.iter()),
+            Containment::Intersecting => {
+                let size = chunks.size();
+                if size == Vector3::new(1, 1, 1) {
+                    visible.push(chunks.start);
+                    return
+                }
+
+                // Split along the longest axis and test both halves
+                let (mut first, mut second) = (chunks, chunks);
+                if size.x >= size.y && size.x >= size.z {
+                    first.end.x = chunks.start.x + size.x / 2 - 1;
+                    second.start.x = first.end.x + 1;
+                } else if size.y >= size.z {
+                    first.end.y = chunks.start.y + size.y / 2 - 1;
+                    second.start.y = first.end.y + 1;
+                } else {
+                    first.end.z = chunks.start.z + size.z / 2 - 1;
+                    second.start.z = first.end.z + 1;
+                }
+
+                self.cull_chunks_recursive(first, chunk_size, visible);
+                self.cull_chunks_recursive(second, chunk_size, visible);
+            },
+        }
+    }
+}
+
+fn distance(plane: Vector4<f32>, point: Point3<f32>) -> f32 {
+    plane.x * point.x + plane.y * point.y + plane.z * point.z + plane.w
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point3, Vector2, Vector3, Quaternion, One},
+
+        camera::{RenderCamera, Containment},
+        grid::{Range},
+    };
+
+    fn camera() -> RenderCamera {
+        RenderCamera::new(Point3::new(0.0, 0.0, 0.0), Quaternion::one(), Vector2::new(100, 100))
+    }
+
+    #[test]
+    fn shapes_are_tested_against_frustum() {
+        let frustum = camera().frustum();
+
+        assert_eq!(frustum.test_point(Point3::new(0.0, 0.0, -10.0)), Containment::Inside);
+        assert_eq!(frustum.test_point(Point3::new(0.0, 0.0, 10.0)), Containment::Outside);
+        assert_eq!(frustum.test_point(Point3::new(11.0, 0.0, -10.0)), Containment::Outside);
+
+        assert_eq!(
+            frustum.test_sphere(Point3::new(0.0, 0.0, -10.0), 1.0), Containment::Inside,
+        );
+        assert_eq!(
+            frustum.test_sphere(Point3::new(10.5, 0.0, -10.0), 1.0), Containment::Intersecting,
+        );
+        assert_eq!(
+            frustum.test_sphere(Point3::new(0.0, 0.0, 5.0), 1.0), Containment::Outside,
+        );
+
+        let aabb = |min: [f32; 3], max: [f32; 3]| frustum.test_aabb(min.into(), max.into());
+        assert_eq!(aabb([-1.0, -1.0, -6.0], [1.0, 1.0, -4.0]), Containment::Inside);
+        assert_eq!(aabb([-1.0, -1.0, -1.0], [1.0, 1.0, 1.0]), Containment::Intersecting);
+        assert_eq!(aabb([-1.0, -1.0, 1.0], [1.0, 1.0, 2.0]), Containment::Outside);
+        assert_eq!(aabb([-1.0, -1.0, -2000.0], [1.0, 1.0, -1500.0]), Containment::Outside);
+    }
+
+    #[test]
+    fn chunk_culling_matches_testing_every_chunk() {
+        let frustum = camera().frustum();
+        let chunk_size = Vector3::new(16.0, 16.0, 16.0);
+        let chunks = Range::new_dim3(-8, -4, -8, 7, 3, 7);
+
+        let mut culled = frustum.cull_chunks(chunks, chunk_size);
+        culled.sort_by_key(|c| (c.x, c.y, c.z));
+        let mut expected: Vec<_> = chunks.iter()
+            .filter(|c| {
+                let min = Point3::new(c.x as f32, c.y as f32, c.z as f32) * 16.0;
+                frustum.test_aabb(min, min + chunk_size) != Containment::Outside
+            })
+            .collect();
+        expected.sort_by_key(|c| (c.x, c.y, c.z));
+
+        assert_eq!(culled, expected);
+        assert!(!culled.is_empty() && culled.len() < chunks.volume());
+    }
+}
@@ -1,10 +1,21 @@
+mod animation;
+mod controller;
+mod frustum;
+
+pub use self::{
+    animation::{Keyframe, Interpolation, CameraPath, CameraShake},
+    controller::{LookSettings, FlyController, WalkController, OrbitController},
+    frustum::{Frustum, Containment},
+};
+
 use {
     cgmath::{
         EuclideanSpace, SquareMatrix, Rotation3, Transform, InnerSpace,
-        Point2, Vector2, Point3, Vector3, Matrix4, Quaternion, PerspectiveFov, Rad,
+        Point2, Vector2, Point3, Vector3, Matrix4, Quaternion, PerspectiveFov, Ortho, Rad,
     },
 };
 
+#[derive(Clone, Copy, Debug)]
 pub struct PitchYawCamera {
     pub pitch: Rad<f32>,
     pub yaw: Rad<f32>,
@@ -19,13 +30,14 @@ impl PitchYawCamera {
     }
 
     pub fn handle_mouse_motion(&mut self, relative: Vector2<i32>) {
-        let sensitivity = 0.0025;
-
-        self.yaw.0 += relative.x as f32 * -sensitivity;
-        self.pitch.0 += relative.y as f32 * -sensitivity;
+        self.look(relative.cast().unwrap(), &LookSettings::default());
+    }
 
-        let limit = ::std::f32::consts::PI * 0.475;
-        self.pitch.0 = self.pitch.0.max(-limit).min(limit);
+    /// Turns the camera by a mouse movement in pixels.
+    pub fn look(&mut self, relative: Vector2<f32>, settings: &LookSettings) {
+        let (yaw, pitch) = settings.apply(relative);
+        self.yaw += yaw;
+        self.pitch = settings.clamp_pitch(self.pitch + pitch);
     }
 
     pub fn to_rotation(&self) -> Quaternion<f32> {
@@ -44,6 +56,7 @@ impl PitchYawCamera {
     }
 }
 
+#[derive(Clone, Copy, Debug)]
 pub struct OrbitingCamera {
     pub focus: Point3<f32>,
     pub pitch: Rad<f32>,
@@ -80,10 +93,62 @@ impl OrbitingCamera {
     }
 }
 
+/// How a camera projects the world onto the screen.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Projection {
+    /// Further away things look smaller, with `fov` being the horizontal field of view.
+    Perspective {
+        fov: Rad<f32>,
+        near: f32,
+        far: f32,
+    },
+    /// Things look the same size at any distance, with `height` world units visible vertically
+    /// at a zoom of 1. Higher zoom shows less of the world.
+    Orthographic {
+        height: f32,
+        zoom: f32,
+        near: f32,
+        far: f32,
+    },
+}
+
+impl Projection {
+    /// A perspective projection with a 90 degree horizontal field of view.
+    pub fn perspective() -> Self {
+        Projection::Perspective {
+            fov: Rad(::std::f32::consts::PI / 2.0),
+            near: 0.2,
+            far: 1000.0,
+        }
+    }
+
+    /// An orthographic projection showing `height` world units vertically. Near is -1, so
+    /// things up to one unit behind the camera's position are still visible, and rays from
+    /// `pixel_to_ray` start one unit behind the camera.
+    pub fn orthographic(height: f32) -> Self {
+        Projection::Orthographic {
+            height,
+            zoom: 1.0,
+            near: -1.0,
+            far: 1000.0,
+        }
+    }
+}
+
+/// A rectangle of the window, in pixels.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Viewport {
+    pub position: Point2<i32>,
+    pub size: Vector2<u32>,
+}
+
 pub struct RenderCamera {
     pub position: Point3<f32>,
     pub rotation: Quaternion<f32>,
     pub window_size: Vector2<u32>,
+    pub projection: Projection,
+    /// The part of the window the camera renders to, or `None` for the entire window.
+    pub viewport: Option<Viewport>,
 }
 
 impl RenderCamera {
@@ -94,27 +159,61 @@ impl RenderCamera {
             position,
             rotation,
             window_size,
+            projection: Projection::perspective(),
+            viewport: None,
         }
     }
 
+    pub fn with_projection(mut self, projection: Projection) -> Self {
+        self.projection = projection;
+        self
+    }
+
+    pub fn with_viewport(mut self, viewport: Viewport) -> Self {
+        self.viewport = Some(viewport);
+        self
+    }
+
+    /// The part of the window the camera renders to.
+    pub fn viewport(&self) -> Viewport {
+        self.viewport.unwrap_or(Viewport {
+            position: Point2::new(0, 0),
+            size: self.window_size,
+        })
+    }
+
     pub fn view_matrix_inverse(&self) -> Matrix4<f32> {
         let rotation: Matrix4<f32> = self.rotation.into();
         Matrix4::from_translation(self.position.to_vec()) * rotation
     }
 
     pub fn projection_matrix(&self) -> Matrix4<f32> {
-        let h_fov = ::std::f32::consts::PI / 2.0; // 90 deg
-        let v_fov = horizontal_to_vertical_fov(h_fov, self.window_size);
-
-        let ratio = self.window_size.x as f32 / self.window_size.y as f32;
-        let projection = PerspectiveFov {
-            fovy: Rad(v_fov),
-            aspect: ratio,
-            near: 0.2,
-            far: 1000.0,
-        };
+        let size = self.viewport().size;
+        let ratio = size.x as f32 / size.y as f32;
 
-        projection.into()
+        match self.projection {
+            Projection::Perspective { fov, near, far } => {
+                let v_fov = horizontal_to_vertical_fov(fov.0, size);
+                PerspectiveFov {
+                    fovy: Rad(v_fov),
+                    aspect: ratio,
+                    near,
+                    far,
+                }.into()
+            },
+            Projection::Orthographic { height, zoom, near, far } => {
+                let half_height = height / zoom / 2.0;
+                let half_width = half_height * ratio;
+                Ortho {
+                    left: -half_width,
+                    right: half_width,
+                    bottom: -half_height,
+                    top: half_height,
+                    near,
+                    far,
+                }.into()
+            },
+        }
     }
 
     pub fn model_view_matrix(&self) -> Matrix4<f32> {
@@ -125,16 +224,77 @@ impl RenderCamera {
         transform
     }
 
+    /// The volume of space this camera can see.
+    pub fn frustum(&self) -> Frustum {
+        Frustum::from_matrix(self.model_view_matrix())
+    }
+
+    /// The volume of space visible within a rectangle of pixels, between two opposite corners
+    /// of the rectangle. Useful for selecting everything within a dragged box.
+    pub fn pixel_rect_to_frustum(&self, a: Point2<i32>, b: Point2<i32>) -> Frustum {
+        // Both corner pixels are part of the rectangle, so it ends at the far side of the
+        // larger one
+        let min = self.pixel_to_ndc(Point2::new(a.x.min(b.x) as f32, a.y.max(b.y) as f32 + 1.0));
+        let max = self.pixel_to_ndc(Point2::new(a.x.max(b.x) as f32 + 1.0, a.y.min(b.y) as f32));
+
+        // Stretch the rectangle to cover the entire clip space
+        let scale = Vector2::new(2.0 / (max.x - min.x), 2.0 / (max.y - min.y));
+        let stretch = Matrix4::from_translation(Vector3::new(
+            -(max.x + min.x) / (max.x - min.x),
+            -(max.y + min.y) / (max.y - min.y),
+            0.0,
+        )) * Matrix4::from_nonuniform_scale(scale.x, scale.y, 1.0);
+
+        Frustum::from_matrix(stretch * self.model_view_matrix())
+    }
+
+    /// Finds the pixel a position in the world is drawn at. Returns `None` for positions behind
+    /// the camera or before its near plane. Positions outside of the viewport still give a
+    /// pixel, outside of the viewport.
+    pub fn world_to_pixel(&self, position: Point3<f32>) -> Option<Point2<f32>> {
+        let clip = self.model_view_matrix() * position.to_homogeneous();
+        if clip.w <= 0.0 || clip.z < -clip.w {
+            return None
+        }
+
+        let viewport = self.viewport();
+        Some(Point2::new(
+            viewport.position.x as f32 + (clip.x / clip.w * 0.5 + 0.5) * viewport.size.x as f32,
+            viewport.position.y as f32 + (0.5 - clip.y / clip.w * 0.5) * viewport.size.y as f32,
+        ))
+    }
+
+    /// Converts a value from the depth buffer, from 0 to 1, into the distance from the camera
+    /// along its view direction.
+    pub fn depth_to_distance(&self, depth: f32) -> f32 {
+        let ndc = depth * 2.0 - 1.0;
+
+        match self.projection {
+            Projection::Perspective { near, far, .. } =>
+                2.0 * far * near / (far + near - ndc * (far - near)),
+            Projection::Orthographic { near, far, .. } =>
+                near + depth * (far - near),
+        }
+    }
+
+    /// Finds the ray going through a pixel of the window. Perspective rays all start at the
+    /// camera's position, orthographic rays all go in the same direction but start at the
+    /// pixel's position on the near plane.
     pub fn pixel_to_ray(&self, pixel_position: Point2<i32>) -> Ray {
         let proj = self.projection_matrix().invert().unwrap();
         let view = self.view_matrix_inverse();
 
         // Get the clip position of the cursor
-        let ray_clip = Vector3::new(
-            (pixel_position.x as f32 / self.window_size.x as f32) * 2.0 - 1.0,
-            1.0 - (pixel_position.y as f32 / self.window_size.y as f32) * 2.0,
-            -1.0,
-        );
+        let ndc = self.pixel_to_ndc(pixel_position.cast().unwrap());
+        let ray_clip = Vector3::new(ndc.x, ndc.y, -1.0);
+
+        if let Projection::Orthographic { .. } = self.projection {
+            let origin = view.transform_point(proj.transform_point(Point3::from_vec(ray_clip)));
+            return Ray {
+                origin,
+                direction: (self.rotation * -Vector3::unit_z()).normalize(),
+            }
+        }
 
         // Convert clip cursor to view cursor
         let mut ray_eye = proj.transform_vector(ray_clip);
@@ -149,6 +309,18 @@ impl RenderCamera {
             direction: ray_world,
         }
     }
+
+    /// Converts a pixel of the window to normalized device coordinates, ranging from -1 to 1
+    /// over the viewport.
+    fn pixel_to_ndc(&self, pixel_position: Point2<f32>) -> Point2<f32> {
+        let viewport = self.viewport();
+        let relative = pixel_position - viewport.position.cast().unwrap();
+
+        Point2::new(
+            (relative.x / viewport.size.x as f32) * 2.0 - 1.0,
+            1.0 - (relative.y / viewport.size.y as f32) * 2.0,
+        )
+    }
 }
 
 fn horizontal_to_vertical_fov(h_fov: f32, window_size: Vector2<u32>) -> f32 {
@@ -160,3 +332,88 @@ pub struct Ray {
     pub origin: Point3<f32>,
     pub direction: Vector3<f32>,
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Point3, Vector2, Vector3, Quaternion, Transform, One},
+
+        camera::{RenderCamera, Projection, Viewport, Containment},
+    };
+
+    fn assert_close(a: Vector3<f32>, b: Vector3<f32>) {
+        let difference = a - b;
+        let is_close = difference.x.abs() < 1e-4 && difference.y.abs() < 1e-4
+            && difference.z.abs() < 1e-4;
+        assert!(is_close, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn perspective_rays_start_at_camera() {
+        let camera = RenderCamera::new(
+            Point3::new(1.0, 2.0, 3.0), Quaternion::one(), Vector2::new(800, 600),
+        );
+
+        let center = camera.pixel_to_ray(Point2::new(400, 300));
+        assert_eq!(center.origin, camera.position);
+        assert_close(center.direction, Vector3::new(0.0, 0.0, -1.0));
+
+        // A 90 degree horizontal field of view puts the right edge at 45 degrees
+        let edge = camera.pixel_to_ray(Point2::new(800, 300));
+        assert_close(edge.direction, Vector3::new(1.0, 0.0, -1.0) * 0.5f32.sqrt());
+    }
+
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let camera = RenderCamera::new(
+            Point3::new(1.0, 2.0, 3.0), Quaternion::one(), Vector2::new(800, 400),
+        ).with_projection(Projection::Orthographic {
+            height: 10.0,
+            zoom: 2.0,
+            near: 0.0,
+            far: 100.0,
+        });
+
+        let center = camera.pixel_to_ray(Point2::new(400, 200));
+        let corner = camera.pixel_to_ray(Point2::new(800, 0));
+        assert_close(center.direction, Vector3::new(0.0, 0.0, -1.0));
+        assert_close(corner.direction, center.direction);
+        assert_close(center.origin - camera.position, Vector3::new(0.0, 0.0, 0.0));
+        assert_close(corner.origin - camera.position, Vector3::new(5.0, 2.5, 0.0));
+
+        // Points along the view direction end up at the center of the screen at any distance
+        let clip = camera.model_view_matrix().transform_point(Point3::new(1.0, 2.0, -50.0));
+        assert!(clip.x.abs() < 1e-5 && clip.y.abs() < 1e-5);
+
+        // The default orthographic projection starts rays just behind the camera
+        let camera = camera.with_projection(Projection::orthographic(10.0));
+        let center = camera.pixel_to_ray(Point2::new(400, 200));
+        assert_close(center.origin - camera.position, Vector3::new(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn world_positions_project_to_pixels() {
+        let camera = RenderCamera::new(
+            Point3::new(0.0, 0.0, 0.0), Quaternion::one(), Vector2::new(800, 600),
+        ).with_viewport(Viewport { position: Point2::new(100, 50), size: Vector2::new(200, 100) });
+
+        let center = camera.world_to_pixel(Point3::new(0.0, 0.0, -10.0)).unwrap();
+        assert!((center.x - 200.0).abs() < 1e-3 && (center.y - 100.0).abs() < 1e-3);
+        let right = camera.world_to_pixel(Point3::new(10.0, 0.0, -10.0)).unwrap();
+        assert!((right.x - 300.0).abs() < 1e-3);
+        assert_eq!(camera.world_to_pixel(Point3::new(0.0, 0.0, 10.0)), None);
+
+        // Rays through a pixel hit positions drawn at that pixel
+        let ray = camera.pixel_to_ray(Point2::new(250, 75));
+        let pixel = camera.world_to_pixel(ray.origin + ray.direction * 20.0).unwrap();
+        assert!((pixel.x - 250.0).abs() < 1e-2 && (pixel.y - 75.0).abs() < 1e-2);
+
+        let clip = camera.model_view_matrix().transform_point(Point3::new(0.0, 0.0, -25.0));
+        assert!((camera.depth_to_distance(clip.z * 0.5 + 0.5) - 25.0).abs() < 1e-2);
+
+        // Selecting the right half of the viewport
+        let selection = camera.pixel_rect_to_frustum(Point2::new(299, 149), Point2::new(200, 50));
+        assert_eq!(selection.test_point(Point3::new(5.0, 0.0, -10.0)), Containment::Inside);
+        assert_eq!(selection.test_point(Point3::new(-5.0, 0.0, -10.0)), Containment::Outside);
+    }
+}
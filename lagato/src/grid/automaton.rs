@@ -0,0 +1,297 @@
+use {
+    std::mem,
+
+    grid::{Dim, Grid, Connectivity},
+};
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// How cells beyond the edges of the grid are seen by neighborhoods.
+pub enum Edges<Cell> {
+    /// The grid wraps around, cells past one edge come from the opposite edge.
+    Wrap,
+    /// Cells past an edge are the same as the closest cell on that edge.
+    Clamp,
+    /// Cells past the edges all have the same value.
+    Constant(Cell),
+}
+
+/// Runs a cellular automaton over a grid. Every step, a rule calculates the new value of every
+/// cell from its current value and its neighbors, reading only the values from before the
+/// step.
+pub struct Automaton<Cell, D: Dim> {
+    current: Grid<Cell, D>,
+    next: Grid<Cell, D>,
+    edges: Edges<Cell>,
+    offsets: Vec<D::Vector>,
+}
+
+impl<Cell: Clone, D: Dim> Automaton<Cell, D> {
+    /// Creates an automaton starting from the cells of a grid. The grid's change tracking is
+    /// stopped, as steps replace all cells at once.
+    pub fn new(mut grid: Grid<Cell, D>, connectivity: Connectivity, edges: Edges<Cell>) -> Self {
+        grid.stop_tracking_changes();
+        let next = Grid::from_storage(grid.size(), grid.storage().clone()).unwrap();
+
+        Automaton {
+            current: grid,
+            next,
+            edges,
+            offsets: D::neighbor_offsets(connectivity),
+        }
+    }
+
+    pub fn grid(&self) -> &Grid<Cell, D> {
+        &self.current
+    }
+
+    /// Gives access to the current cells, for changing them in between steps. The grid may also
+    /// be replaced or resized, the next step will continue at the new size. Change tracking
+    /// started on this grid stays with the current cells, with every step recording all cells
+    /// as changed.
+    pub fn grid_mut(&mut self) -> &mut Grid<Cell, D> {
+        &mut self.current
+    }
+
+    pub fn into_grid(self) -> Grid<Cell, D> {
+        self.current
+    }
+
+    /// Advances the automaton by one step.
+    pub fn step<F: FnMut(&Cell, &Neighborhood<Cell, D>) -> Cell>(&mut self, mut rule: F) {
+        self.match_next_size();
+        let size = self.current.size();
+
+        for (index, cell) in self.next.cells_mut().iter_mut().enumerate() {
+            let neighborhood = Neighborhood {
+                grid: &self.current,
+                edges: &self.edges,
+                offsets: &self.offsets,
+                position: D::position(index, size),
+            };
+            *cell = rule(neighborhood.get_at(neighborhood.position), &neighborhood);
+        }
+
+        self.swap_grids();
+    }
+
+    pub fn steps<F: FnMut(&Cell, &Neighborhood<Cell, D>) -> Cell>(
+        &mut self, amount: usize, mut rule: F,
+    ) {
+        for _ in 0..amount {
+            self.step(&mut rule);
+        }
+    }
+
+    /// Advances the automaton by one step, calculating cells on multiple threads.
+    #[cfg(feature = "parallel")]
+    pub fn step_parallel<F>(&mut self, rule: F) where
+        Cell: Send + Sync,
+        F: Fn(&Cell, &Neighborhood<Cell, D>) -> Cell + Sync,
+    {
+        self.match_next_size();
+        let size = self.current.size();
+        let current = &self.current;
+        let edges = &self.edges;
+        let offsets = &self.offsets;
+
+        self.next.cells_mut().par_iter_mut().enumerate().for_each(|(index, cell)| {
+            let neighborhood = Neighborhood {
+                grid: current,
+                edges,
+                offsets,
+                position: D::position(index, size),
+            };
+            *cell = rule(neighborhood.get_at(neighborhood.position), &neighborhood);
+        });
+
+        self.swap_grids();
+    }
+
+    #[cfg(feature = "parallel")]
+    pub fn steps_parallel<F>(&mut self, amount: usize, rule: F) where
+        Cell: Send + Sync,
+        F: Fn(&Cell, &Neighborhood<Cell, D>) -> Cell + Sync,
+    {
+        for _ in 0..amount {
+            self.step_parallel(&rule);
+        }
+    }
+
+    fn swap_grids(&mut self) {
+        // Change tracking belongs to the current cells, not to whichever buffer holds them
+        let tracker = self.current.change_tracker_mut().take();
+        mem::swap(&mut self.current, &mut self.next);
+        *self.current.change_tracker_mut() = tracker;
+
+        let range = self.current.range();
+        self.current.record_changes(range);
+    }
+
+    fn match_next_size(&mut self) {
+        // The current grid may have been swapped out through `grid_mut`, all of next's cells get
+        // overwritten so its contents don't matter
+        if self.next.size() != self.current.size() {
+            let storage = self.current.storage().clone();
+            self.next = Grid::from_storage(self.current.size(), storage).unwrap();
+        }
+    }
+}
+
+/// The cells around the cell a rule is calculating the new value for.
+pub struct Neighborhood<'a, Cell: 'a, D: Dim + 'a> {
+    grid: &'a Grid<Cell, D>,
+    edges: &'a Edges<Cell>,
+    offsets: &'a [D::Vector],
+    position: D::Point,
+}
+
+impl<'a, Cell, D: Dim> Neighborhood<'a, Cell, D> {
+    pub fn position(&self) -> D::Point {
+        self.position
+    }
+
+    /// Gets the cell at an offset from the center cell, handling edges as configured.
+    pub fn get(&self, offset: D::Vector) -> &'a Cell {
+        self.get_at(self.position + offset)
+    }
+
+    /// Iterates over the neighbors of the center cell, for the automaton's connectivity.
+    pub fn iter<'b>(&'b self) -> impl Iterator<Item=&'a Cell> + 'b {
+        self.offsets.iter().map(move |offset| self.get(*offset))
+    }
+
+    /// Counts the neighbors matching a predicate.
+    pub fn count<F: FnMut(&Cell) -> bool>(&self, mut predicate: F) -> usize {
+        self.iter().filter(|cell| predicate(cell)).count()
+    }
+
+    fn get_at(&self, position: D::Point) -> &'a Cell {
+        if let Ok(cell) = self.grid.get(position) {
+            return cell
+        }
+
+        let size = self.grid.size();
+        let mut offset = position - D::start();
+        for axis in D::axes() {
+            let value = D::component(offset, *axis);
+            let length = D::component(size, *axis);
+            let value = match *self.edges {
+                Edges::Wrap => value.rem_euclid(length),
+                Edges::Clamp => value.max(0).min(length - 1),
+                Edges::Constant(ref cell) => return cell,
+            };
+            offset = D::with_component(offset, *axis, value);
+        }
+
+        self.grid.get(D::start() + offset).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Vector2},
+
+        grid::{
+            Grid, Tiles, Range, Connectivity, Automaton, Edges, Neighborhood, Dim2, Tracking,
+        },
+    };
+
+    fn life(alive: &bool, neighborhood: &Neighborhood<bool, Dim2>) -> bool {
+        let neighbors = neighborhood.count(|n| *n);
+        neighbors == 3 || (*alive && neighbors == 2)
+    }
+
+    fn alive(tiles: &Tiles<bool>) -> Vec<Point2<i32>> {
+        tiles.iter_pos().filter(|p| *tiles.get(*p).unwrap()).collect()
+    }
+
+    #[test]
+    fn blinker_oscillates_across_wrapped_edges() {
+        // A blinker on the edge, only works if the other side is seen as its neighbors
+        let mut tiles: Tiles<bool> = Grid::empty(Vector2::new(5, 5));
+        tiles.fill(Range::new_dim2(0, 2, 0, 2), true);
+        tiles.fill(Range::new_dim2(4, 2, 4, 2), true);
+        tiles.fill(Range::new_dim2(1, 2, 1, 2), true);
+        let start = alive(&tiles);
+
+        let mut automaton = Automaton::new(tiles, Connectivity::All, Edges::Wrap);
+        automaton.step(life);
+        assert_eq!(alive(automaton.grid()), vec![
+            Point2::new(0, 1), Point2::new(0, 2), Point2::new(0, 3),
+        ]);
+
+        automaton.steps(3, life);
+        assert_eq!(alive(automaton.grid()), start);
+    }
+
+    #[test]
+    fn edges_are_clamped_or_constant() {
+        let mut tiles: Tiles<u8> = Grid::empty(Vector2::new(3, 1));
+        *tiles.get_mut(Point2::new(0, 0)).unwrap() = 5;
+
+        let shift_right = |_: &u8, n: &Neighborhood<u8, Dim2>| *n.get(Vector2::new(-1, 0));
+
+        let mut clamped = Automaton::new(tiles, Connectivity::Orthogonal, Edges::Clamp);
+        clamped.steps(2, shift_right);
+        assert_eq!(clamped.grid().get(Point2::new(2, 0)), Ok(&5));
+        assert_eq!(clamped.grid().get(Point2::new(0, 0)), Ok(&5));
+
+        let mut constant = Automaton::new(
+            clamped.into_grid(), Connectivity::Orthogonal, Edges::Constant(9),
+        );
+        constant.step(shift_right);
+        assert_eq!(constant.grid().get(Point2::new(0, 0)), Ok(&9));
+        assert_eq!(constant.grid().get(Point2::new(1, 0)), Ok(&5));
+    }
+
+    #[test]
+    fn replaced_grids_keep_stepping() {
+        let mut automaton = Automaton::new(
+            Grid::empty(Vector2::new(3, 3)), Connectivity::All, Edges::Wrap,
+        );
+        automaton.step(life);
+
+        let mut tiles: Tiles<bool> = Grid::empty(Vector2::new(5, 5));
+        tiles.fill(Range::new_dim2(1, 2, 3, 2), true);
+        *automaton.grid_mut() = tiles;
+        automaton.step(life);
+
+        assert_eq!(automaton.grid().size(), Vector2::new(5, 5));
+        assert_eq!(alive(automaton.grid()), vec![
+            Point2::new(2, 1), Point2::new(2, 2), Point2::new(2, 3),
+        ]);
+    }
+
+    #[test]
+    fn change_tracking_follows_the_current_grid() {
+        let mut automaton = Automaton::new(
+            Grid::empty(Vector2::new(4, 4)), Connectivity::All, Edges::Wrap,
+        );
+        automaton.grid_mut().track_changes(Tracking::Chunks(Vector2::new(2, 2)));
+
+        automaton.steps(3, life);
+        assert!(automaton.grid().is_tracking_changes());
+        assert_eq!(automaton.grid_mut().drain_changes().len(), 4);
+        assert!(!automaton.grid().has_changes());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_steps_match_serial_steps() {
+        let mut tiles: Tiles<bool> = Grid::empty(Vector2::new(32, 32));
+        for position in tiles.range().iter() {
+            *tiles.get_mut(position).unwrap() = (position.x * 7 + position.y * 13) % 5 == 0;
+        }
+        let copy = Grid::from_storage(tiles.size(), tiles.storage().clone()).unwrap();
+
+        let mut serial = Automaton::new(tiles, Connectivity::All, Edges::Wrap);
+        let mut parallel = Automaton::new(copy, Connectivity::All, Edges::Wrap);
+        serial.steps(10, life);
+        parallel.steps_parallel(10, life);
+
+        assert_eq!(alive(serial.grid()), alive(parallel.grid()));
+    }
+}
@@ -0,0 +1,157 @@
+use {
+    std::collections::{HashSet},
+
+    grid::{Dim, Grid, Range},
+};
+
+/// What a grid records when its cells change.
+pub enum Tracking<D: Dim> {
+    /// Every changed position is recorded separately.
+    Positions,
+    /// Only which chunks of the given size contain changes is recorded, which is cheaper when
+    /// changes get handled per chunk anyway, such as when rebuilding meshes.
+    Chunks(D::Vector),
+}
+
+/// Records changes made to a grid, in the order they were first made.
+pub(crate) struct ChangeTracker<D: Dim> {
+    tracking: Tracking<D>,
+    /// Changed positions or chunks, depending on what's being tracked.
+    changed: Vec<D::Point>,
+    seen: HashSet<D::Point>,
+}
+
+impl<D: Dim> ChangeTracker<D> {
+    pub(crate) fn record(&mut self, position: D::Point) {
+        let key = match self.tracking {
+            Tracking::Positions => position,
+            Tracking::Chunks(chunk_size) => D::to_chunk(position, chunk_size).0,
+        };
+
+        if self.seen.insert(key) {
+            self.changed.push(key);
+        }
+    }
+}
+
+impl<Cell, D: Dim, S> Grid<Cell, D, S> {
+    /// Starts recording which cells are changed through `set`, `get_mut` and any operations
+    /// using those. Any changes recorded before are discarded.
+    /// Cells retrieved with `get_mut` are always recorded, whether they're actually modified or
+    /// not.
+    /// Panics if a chunk size isn't larger than zero along every axis.
+    pub fn track_changes(&mut self, tracking: Tracking<D>) {
+        if let Tracking::Chunks(chunk_size) = tracking {
+            assert!(
+                D::axes().iter().all(|axis| D::component(chunk_size, *axis) > 0),
+                "Chunk size must be larger than zero along every axis",
+            );
+        }
+
+        *self.change_tracker_mut() = Some(ChangeTracker {
+            tracking,
+            changed: Vec::new(),
+            seen: HashSet::new(),
+        });
+    }
+
+    /// Stops recording changes, discarding any that weren't drained yet.
+    pub fn stop_tracking_changes(&mut self) {
+        *self.change_tracker_mut() = None;
+    }
+
+    pub fn is_tracking_changes(&self) -> bool {
+        self.change_tracker().is_some()
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.change_tracker().as_ref().map(|t| !t.changed.is_empty()).unwrap_or(false)
+    }
+
+    /// Returns the ranges changed since tracking started or changes were last drained, and
+    /// clears them. When tracking positions these are single positions, when tracking chunks
+    /// these are the parts of changed chunks within the grid. Use `Range::coalesce` to merge
+    /// them into fewer, larger ranges.
+    pub fn drain_changes(&mut self) -> Vec<Range<D>> {
+        let grid_range = self.range();
+        let tracker = match *self.change_tracker_mut() {
+            Some(ref mut tracker) => tracker,
+            None => return Vec::new(),
+        };
+        tracker.seen.clear();
+
+        match tracker.tracking {
+            Tracking::Positions => tracker.changed.drain(..)
+                .map(|position| Range::new(position, position))
+                .collect(),
+            Tracking::Chunks(chunk_size) => tracker.changed.drain(..)
+                .filter_map(|chunk| {
+                    let chunk_range = Range::new(
+                        D::from_chunk(chunk, D::start(), chunk_size),
+                        D::from_chunk(chunk, D::end(chunk_size), chunk_size),
+                    );
+                    chunk_range.intersection(&grid_range)
+                })
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Vector2, Vector3},
+
+        grid::{Grid, Tiles, Voxels, Range, Tracking},
+    };
+
+    #[test]
+    fn changed_positions_coalesce_into_ranges() {
+        let mut tiles: Tiles<u8> = Grid::empty(Vector2::new(8, 8));
+        tiles.set(Point2::new(0, 0), 1).unwrap();
+        assert!(!tiles.has_changes());
+
+        tiles.track_changes(Tracking::Positions);
+        tiles.fill(Range::new_dim2(1, 1, 3, 2), 2);
+        tiles.set(Point2::new(1, 1), 3).unwrap();
+        *tiles.get_mut(Point2::new(7, 7)).unwrap() = 4;
+
+        let changes = tiles.drain_changes();
+        assert_eq!(changes.len(), 7);
+        assert_eq!(changes[0], Range::new_dim2(1, 1, 1, 1));
+        assert!(!tiles.has_changes());
+
+        let coalesced = Range::coalesce(&changes);
+        assert_eq!(coalesced.len(), 2);
+        assert!(coalesced.contains(&Range::new_dim2(1, 1, 3, 2)));
+        assert!(coalesced.contains(&Range::new_dim2(7, 7, 7, 7)));
+    }
+
+    #[test]
+    fn chunk_tracking_records_chunk_ranges() {
+        let mut voxels: Voxels<bool> = Grid::empty(Vector3::new(20, 8, 8));
+        voxels.track_changes(Tracking::Chunks(Vector3::new(8, 8, 8)));
+
+        voxels.fill(Range::new_dim3(6, 0, 0, 17, 0, 0), true);
+
+        let changes = voxels.drain_changes();
+        assert_eq!(changes, vec![
+            Range::new_dim3(0, 0, 0, 7, 7, 7),
+            Range::new_dim3(8, 0, 0, 15, 7, 7),
+            Range::new_dim3(16, 0, 0, 19, 7, 7),
+        ]);
+        assert_eq!(Range::coalesce(&changes), vec![Range::new_dim3(0, 0, 0, 19, 7, 7)]);
+
+        voxels.stop_tracking_changes();
+        voxels.fill(Range::new_dim3(0, 0, 0, 1, 1, 1), false);
+        assert!(voxels.drain_changes().is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk size must be larger than zero")]
+    fn invalid_chunk_tracking_is_rejected() {
+        let mut tiles: Tiles<u8> = Grid::empty(Vector2::new(8, 8));
+        tiles.track_changes(Tracking::Chunks(Vector2::new(4, 4)));
+        tiles.track_changes(Tracking::Chunks(Vector2::new(4, -4)));
+    }
+}
@@ -0,0 +1,167 @@
+use {
+    std::collections::{HashMap},
+
+    grid::{Dim, Grid, Range, Error},
+};
+
+/// An unbounded grid made up of fixed-size `Grid` chunks, which are created as they're written
+/// to. Unlike `Grid`, positions can be negative.
+pub struct ChunkedGrid<Cell, D: Dim> {
+    chunks: HashMap<D::Point, Grid<Cell, D>>,
+    chunk_size: D::Vector,
+}
+
+impl<Cell, D: Dim> ChunkedGrid<Cell, D> {
+    pub fn new(chunk_size: D::Vector) -> Self {
+        assert!(
+            D::is_valid_size(chunk_size) && D::area(chunk_size) > 0,
+            "Chunk size must be larger than zero along every axis",
+        );
+
+        ChunkedGrid {
+            chunks: HashMap::new(),
+            chunk_size,
+        }
+    }
+
+    pub fn chunk_size(&self) -> D::Vector {
+        self.chunk_size
+    }
+
+    /// Splits a position into the coordinates of its chunk and the position within that chunk.
+    pub fn to_chunk(&self, position: D::Point) -> (D::Point, D::Point) {
+        D::to_chunk(position, self.chunk_size)
+    }
+
+    /// Gets the cell at a position, or `None` if the chunk it's in isn't loaded.
+    pub fn get(&self, position: D::Point) -> Option<&Cell> {
+        let (chunk, local) = self.to_chunk(position);
+        self.chunks.get(&chunk).map(|grid| grid.get(local).unwrap())
+    }
+
+    /// Gets the cell at a position, or `None` if the chunk it's in isn't loaded.
+    /// Use `get_or_create_mut` to write to chunks that don't exist yet.
+    pub fn get_mut(&mut self, position: D::Point) -> Option<&mut Cell> {
+        let (chunk, local) = self.to_chunk(position);
+        self.chunks.get_mut(&chunk).map(|grid| grid.get_mut(local).unwrap())
+    }
+
+    pub fn chunk(&self, chunk: D::Point) -> Option<&Grid<Cell, D>> {
+        self.chunks.get(&chunk)
+    }
+
+    pub fn chunk_mut(&mut self, chunk: D::Point) -> Option<&mut Grid<Cell, D>> {
+        self.chunks.get_mut(&chunk)
+    }
+
+    pub fn is_loaded(&self, chunk: D::Point) -> bool {
+        self.chunks.contains_key(&chunk)
+    }
+
+    /// Loads a chunk, replacing and returning the previous chunk at those coordinates if any.
+    pub fn load_chunk(
+        &mut self, chunk: D::Point, grid: Grid<Cell, D>
+    ) -> Result<Option<Grid<Cell, D>>, Error> {
+        if grid.size() != self.chunk_size {
+            return Err(Error::SizeMismatch)
+        }
+
+        Ok(self.chunks.insert(chunk, grid))
+    }
+
+    /// Unloads a chunk, returning it so it can for example be saved.
+    pub fn unload_chunk(&mut self, chunk: D::Point) -> Option<Grid<Cell, D>> {
+        self.chunks.remove(&chunk)
+    }
+
+    /// Iterates over the coordinates of all loaded chunks, in no particular order.
+    pub fn loaded_chunks<'a>(&'a self) -> impl Iterator<Item=D::Point> + 'a {
+        self.chunks.keys().cloned()
+    }
+
+    /// Iterates over all positions in the range, together with their cell if it's in a loaded
+    /// chunk.
+    pub fn iter_range<'a>(
+        &'a self, range: Range<D>
+    ) -> impl Iterator<Item=(D::Point, Option<&'a Cell>)> + 'a {
+        range.iter().map(move |position| (position, self.get(position)))
+    }
+}
+
+impl<Cell: Default, D: Dim> ChunkedGrid<Cell, D> {
+    /// Gets the cell at a position, creating an empty chunk for it if it doesn't exist yet.
+    pub fn get_or_create_mut(&mut self, position: D::Point) -> &mut Cell {
+        let (chunk, local) = self.to_chunk(position);
+        let chunk_size = self.chunk_size;
+        self.chunks.entry(chunk)
+            .or_insert_with(|| Grid::empty(chunk_size))
+            .get_mut(local).unwrap()
+    }
+
+    /// Sets the cell at a position, creating an empty chunk for it if it doesn't exist yet.
+    pub fn set(&mut self, position: D::Point, value: Cell) {
+        *self.get_or_create_mut(position) = value;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Vector2, Point3, Vector3},
+
+        grid::{ChunkedGrid, Grid, Dim2, Dim3, Range, Error},
+    };
+
+    #[test]
+    fn negative_positions_end_up_in_negative_chunks() {
+        let mut grid: ChunkedGrid<i32, Dim3> = ChunkedGrid::new(Vector3::new(16, 16, 16));
+
+        grid.set(Point3::new(-1, 0, 17), 5);
+
+        assert_eq!(grid.get(Point3::new(-1, 0, 17)), Some(&5));
+        assert!(grid.is_loaded(Point3::new(-1, 0, 1)));
+        assert_eq!(grid.loaded_chunks().count(), 1);
+        let chunk = grid.chunk(Point3::new(-1, 0, 1)).unwrap();
+        assert_eq!(chunk.get(Point3::new(15, 0, 1)), Ok(&5));
+    }
+
+    #[test]
+    fn unloaded_chunks_are_not_created_on_read() {
+        let mut grid: ChunkedGrid<bool, Dim2> = ChunkedGrid::new(Vector2::new(8, 8));
+
+        assert_eq!(grid.get(Point2::new(-20, 3)), None);
+        assert_eq!(grid.get_mut(Point2::new(-20, 3)), None);
+        assert_eq!(grid.loaded_chunks().count(), 0);
+
+        let range = Range::new_dim2(-1, -1, 0, 0);
+        assert!(grid.iter_range(range).all(|(_, cell)| cell.is_none()));
+    }
+
+    #[test]
+    fn chunks_can_be_unloaded_and_loaded() {
+        let mut grid: ChunkedGrid<bool, Dim2> = ChunkedGrid::new(Vector2::new(8, 8));
+        grid.set(Point2::new(3, -3), true);
+
+        let chunk = grid.unload_chunk(Point2::new(0, -1)).unwrap();
+        assert_eq!(grid.get(Point2::new(3, -3)), None);
+
+        assert!(grid.load_chunk(Point2::new(0, -1), chunk).unwrap().is_none());
+        assert_eq!(grid.get(Point2::new(3, -3)), Some(&true));
+
+        let wrong_size = Grid::empty(Vector2::new(4, 4));
+        let result = grid.load_chunk(Point2::new(5, 5), wrong_size);
+        assert_eq!(result.err(), Some(Error::SizeMismatch));
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk size must be larger than zero")]
+    fn zero_chunk_sizes_are_rejected() {
+        let _: ChunkedGrid<bool, Dim2> = ChunkedGrid::new(Vector2::new(8, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Chunk size must be larger than zero")]
+    fn negative_chunk_sizes_are_rejected() {
+        let _: ChunkedGrid<bool, Dim2> = ChunkedGrid::new(Vector2::new(-8, -8));
+    }
+}
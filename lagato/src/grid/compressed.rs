@@ -0,0 +1,210 @@
+use {
+    std::io::{self, Read, Write},
+
+    bincode,
+    byteorder::{WriteBytesExt, ReadBytesExt},
+    serde::{Serialize, de::DeserializeOwned},
+
+    grid::{Dim, Grid, Storage},
+};
+
+/// Identifies the compressed grid format, so garbage is rejected early.
+const MAGIC: [u8; 4] = *b"LGRD";
+const VERSION: u8 = 1;
+
+impl<Cell, D, S> Grid<Cell, D, S> where
+    Cell: Serialize + PartialEq,
+    D: Dim,
+    S: Storage<Cell>,
+{
+    /// Writes this grid in a compact binary format, with runs of equal cells stored only once.
+    /// Cells are written in the order of `iter_pos`, without buffering the entire grid.
+    /// Grids without any cells can't be written.
+    pub fn write_compressed<W: Write>(&self, mut writer: W) -> Result<(), FormatError> {
+        let mut positions = self.iter_pos();
+        let mut run = match positions.next() {
+            Some(position) => (self.value(position).unwrap(), 1u64),
+            None => return Err(FormatError::InvalidSize),
+        };
+
+        writer.write_all(&MAGIC)?;
+        writer.write_u8(VERSION)?;
+        bincode::serialize_into(&mut writer, &self.size())?;
+
+        for position in positions {
+            let value = self.value(position).unwrap();
+            if value == run.0 {
+                run.1 += 1;
+            } else {
+                write_run(&mut writer, &run.0, run.1)?;
+                run = (value, 1);
+            }
+        }
+        write_run(&mut writer, &run.0, run.1)?;
+
+        Ok(())
+    }
+}
+
+impl<Cell, D, S> Grid<Cell, D, S> where
+    Cell: DeserializeOwned + Clone,
+    D: Dim,
+    S: Storage<Cell>,
+{
+    /// Reads a grid written by `write_compressed`. Grids with more than `max_area` cells are
+    /// rejected before anything is allocated for them, which protects against untrusted data
+    /// claiming a huge size.
+    pub fn read_compressed<R: Read>(mut reader: R, max_area: usize) -> Result<Self, FormatError> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != MAGIC {
+            return Err(FormatError::InvalidHeader)
+        }
+
+        let version = reader.read_u8()?;
+        if version != VERSION {
+            return Err(FormatError::UnsupportedVersion(version))
+        }
+
+        let size: D::Vector = bincode::deserialize_from(&mut reader)?;
+        let area = D::area(size);
+        let is_negative = D::min(D::start() + size, D::start()) != D::start();
+        if is_negative || area == 0 || area > max_area || !D::is_valid_size(size) {
+            return Err(FormatError::InvalidSize)
+        }
+
+        // The first run tells us what to fill the storage with, so we only need to set the rest
+        let (first, mut remaining) = read_run::<_, Cell>(&mut reader)?;
+        let mut grid: Self = Grid::filled(size, first);
+        let mut value: Option<Cell> = None;
+
+        for position in grid.iter_pos() {
+            if remaining == 0 {
+                let (next, length) = read_run(&mut reader)?;
+                value = Some(next);
+                remaining = length;
+            }
+            remaining -= 1;
+
+            if let Some(ref value) = value {
+                grid.set(position, value.clone()).unwrap();
+            }
+        }
+
+        // Runs that go beyond the grid's size mean the data is corrupt
+        if remaining != 0 {
+            return Err(FormatError::InvalidData)
+        }
+
+        Ok(grid)
+    }
+}
+
+fn write_run<W: Write, Cell: Serialize>(
+    writer: &mut W, value: &Cell, length: u64
+) -> Result<(), FormatError> {
+    write_varint(writer, length)?;
+    bincode::serialize_into(writer, value)?;
+    Ok(())
+}
+
+fn read_run<R: Read, Cell: DeserializeOwned>(reader: &mut R) -> Result<(Cell, u64), FormatError> {
+    let length = read_varint(reader)?;
+    if length == 0 {
+        return Err(FormatError::InvalidData)
+    }
+
+    let value = bincode::deserialize_from(reader)?;
+    Ok((value, length))
+}
+
+/// Writes an integer 7 bits at a time, so short runs only take a single byte.
+fn write_varint<W: Write>(writer: &mut W, mut value: u64) -> io::Result<()> {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            return writer.write_u8(byte)
+        }
+        writer.write_u8(byte | 0x80)?;
+    }
+}
+
+fn read_varint<R: Read>(reader: &mut R) -> Result<u64, FormatError> {
+    let mut value = 0;
+    for shift in (0..64).step_by(7) {
+        let byte = reader.read_u8()?;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value)
+        }
+    }
+
+    Err(FormatError::InvalidData)
+}
+
+#[derive(Debug)]
+pub enum FormatError {
+    Io(io::Error),
+    Encoding(bincode::Error),
+    InvalidHeader,
+    UnsupportedVersion(u8),
+    InvalidSize,
+    InvalidData,
+}
+
+impl From<io::Error> for FormatError {
+    fn from(error: io::Error) -> Self {
+        FormatError::Io(error)
+    }
+}
+
+impl From<bincode::Error> for FormatError {
+    fn from(error: bincode::Error) -> Self {
+        FormatError::Encoding(error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point3, Vector2, Vector3},
+
+        grid::{Grid, Tiles, Voxels, Dim3, Range, PaletteStorage, FormatError},
+    };
+
+    #[test]
+    fn compressed_grids_round_trip() {
+        let mut voxels: Voxels<u16> = Grid::empty(Vector3::new(32, 32, 32));
+        voxels.fill(Range::new_dim3(0, 0, 0, 31, 31, 7), 1);
+        *voxels.get_mut(Point3::new(5, 20, 5)).unwrap() = 7;
+
+        let mut data = Vec::new();
+        voxels.write_compressed(&mut data).unwrap();
+        assert!(data.len() < 64);
+
+        let read: Grid<u16, Dim3, PaletteStorage<u16>> =
+            Grid::read_compressed(data.as_slice(), 1 << 20).unwrap();
+        assert_eq!(read.size(), voxels.size());
+        for position in voxels.iter_pos() {
+            assert_eq!(read.value(position).unwrap(), *voxels.get(position).unwrap());
+        }
+    }
+
+    #[test]
+    fn reading_rejects_bad_data() {
+        let tiles: Tiles<u8> = Grid::filled(Vector2::new(16, 16), 3);
+        let mut data = Vec::new();
+        tiles.write_compressed(&mut data).unwrap();
+
+        let too_large: Result<Tiles<u8>, _> = Grid::read_compressed(data.as_slice(), 100);
+        match too_large { Err(FormatError::InvalidSize) => {}, _ => panic!() }
+
+        let truncated: Result<Tiles<u8>, _> = Grid::read_compressed(&data[..data.len() - 1], 256);
+        match truncated { Err(FormatError::Encoding(_)) => {}, _ => panic!() }
+
+        data[4] = 200;
+        let new_version: Result<Tiles<u8>, _> = Grid::read_compressed(data.as_slice(), 256);
+        match new_version { Err(FormatError::UnsupportedVersion(200)) => {}, _ => panic!() }
+    }
+}
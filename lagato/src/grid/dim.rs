@@ -1,20 +1,143 @@
 use {
+    std::{
+        fmt::{Debug},
+        hash::{Hash},
+        ops::{Add, Sub},
+        vec::{IntoIter},
+    },
     cgmath::{Vector2, Point2, Vector3, Point3},
     serde::{Serialize, de::DeserializeOwned},
 };
 
 pub trait Dim {
-    type Vector: Serialize + DeserializeOwned + Copy;
-    type Point: Serialize + DeserializeOwned + Copy;
+    type Vector: Serialize + DeserializeOwned + Copy + Eq + Debug + Send + Sync
+        + Add<Output=Self::Vector> + Sub<Output=Self::Vector>;
+    type Point: Serialize + DeserializeOwned + Copy + Eq + Hash + Debug + Send + Sync
+        + Add<Self::Vector, Output=Self::Point> + Sub<Self::Vector, Output=Self::Point>
+        + Sub<Self::Point, Output=Self::Vector>;
+    /// A position in continuous space, with cells being one unit large.
+    type FloatPoint: Copy;
 
     fn start() -> Self::Point;
     fn end(size: Self::Vector) -> Self::Point;
 
+    /// The cell containing a continuous position, rounding down.
+    fn floor(position: Self::FloatPoint) -> Self::Point;
+    /// The cell containing a continuous position, rounding up.
+    fn ceil(position: Self::FloatPoint) -> Self::Point;
+
+    /// Creates a vector with all components set to the same value.
+    fn splat(value: i32) -> Self::Vector;
+    /// The axes of this dimension, in the order they're used for indexing.
+    fn axes() -> &'static [Axis];
+    /// Gets the component of a vector along an axis. Panics if the axis isn't part of this
+    /// dimension.
+    fn component(vector: Self::Vector, axis: Axis) -> i32;
+    /// Replaces the component of a vector along an axis. Panics if the axis isn't part of this
+    /// dimension.
+    fn with_component(vector: Self::Vector, axis: Axis, value: i32) -> Self::Vector;
+    /// Component-wise minimum of two points.
+    fn min(a: Self::Point, b: Self::Point) -> Self::Point;
+    /// Component-wise maximum of two points.
+    fn max(a: Self::Point, b: Self::Point) -> Self::Point;
+
     fn area(size: Self::Vector) -> usize;
+    /// Checks if a grid can have this size, as some layouts only support certain sizes. Sizes
+    /// can't be negative along any axis.
+    fn is_valid_size(size: Self::Vector) -> bool {
+        Self::axes().iter().all(|axis| Self::component(size, *axis) >= 0)
+    }
     fn is_in_bounds(position: Self::Point, size: Self::Vector) -> bool;
+    /// Checks if cells are stored in the same order `next` walks through a range, row by row.
+    /// Iterators over ranges rely on this to walk storage without sorting.
+    fn is_row_major() -> bool {
+        true
+    }
 
     fn index(position: Self::Point, size: Self::Vector) -> usize;
+    /// The position stored at an index, the inverse of `index`.
+    fn position(index: usize, size: Self::Vector) -> Self::Point;
     fn next(position: Self::Point, start: Self::Point, end: Self::Point) -> Option<Self::Point>;
+
+    /// Splits a position into the coordinates of the chunk it's in and its position within that
+    /// chunk. Works for negative positions, which end up in negative chunks.
+    fn to_chunk(position: Self::Point, chunk_size: Self::Vector) -> (Self::Point, Self::Point);
+    fn from_chunk(
+        chunk: Self::Point, local: Self::Point, chunk_size: Self::Vector
+    ) -> Self::Point;
+
+    /// The offsets to all neighbors of a cell.
+    fn neighbor_offsets(connectivity: Connectivity) -> Vec<Self::Vector>;
+    /// Iterates over all neighbors of a position, this doesn't do any bounds checking.
+    /// When going over the neighbors of many positions, it's cheaper to use `neighbor_offsets`
+    /// once instead.
+    fn neighbors(position: Self::Point, connectivity: Connectivity) -> Neighbors<Self>
+        where Self: Sized
+    {
+        Neighbors {
+            position,
+            offsets: Self::neighbor_offsets(connectivity).into_iter(),
+        }
+    }
+    /// The minimum amount of steps needed to get from one point to another, moving only to
+    /// neighbors.
+    fn distance(a: Self::Point, b: Self::Point, connectivity: Connectivity) -> i32;
+}
+
+pub struct Neighbors<D: Dim> {
+    position: D::Point,
+    offsets: IntoIter<D::Vector>,
+}
+
+impl<D: Dim> Iterator for Neighbors<D> {
+    type Item = D::Point;
+
+    fn next(&mut self) -> Option<D::Point> {
+        self.offsets.next().map(|offset| self.position + offset)
+    }
+}
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Axis {
+    X,
+    Y,
+    Z,
+}
+
+/// Which cells count as neighbors of a cell, by how many axes they may differ on.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Connectivity {
+    /// Neighbors differ on only one axis, giving 4 neighbors in 2D and 6 in 3D.
+    Orthogonal,
+    /// Neighbors differ on at most two axes, giving 8 neighbors in 2D and 18 in 3D.
+    Edges,
+    /// Neighbors differ on any amount of axes, giving 8 neighbors in 2D and 26 in 3D.
+    All,
+}
+
+impl Connectivity {
+    fn max_axes(self) -> usize {
+        match self {
+            Connectivity::Orthogonal => 1,
+            Connectivity::Edges => 2,
+            Connectivity::All => 3,
+        }
+    }
+
+    fn allows(self, offset: &[i32]) -> bool {
+        let changed = offset.iter().filter(|v| **v != 0).count();
+        changed != 0 && changed <= self.max_axes()
+    }
+
+    fn steps(self, delta: &[i32]) -> i32 {
+        let mut delta: Vec<i32> = delta.iter().map(|v| v.abs()).collect();
+        delta.sort_by(|a, b| b.cmp(a));
+        let sum: i32 = delta.iter().sum();
+
+        // Every step can cover up to max_axes axes, but only one unit along each of them
+        let max_axes = self.max_axes() as i32;
+        delta[0].max((sum + max_axes - 1) / max_axes)
+    }
 }
 
 pub enum Dim2 {}
@@ -22,6 +145,7 @@ pub enum Dim2 {}
 impl Dim for Dim2 {
     type Vector = Vector2<i32>;
     type Point = Point2<i32>;
+    type FloatPoint = Point2<f32>;
 
     fn start() -> Self::Point {
         Point2::new(0, 0)
@@ -31,8 +155,49 @@ impl Dim for Dim2 {
         Point2::new(size.x - 1, size.y - 1)
     }
 
+    fn floor(position: Point2<f32>) -> Point2<i32> {
+        position.map(|v| v.floor() as i32)
+    }
+
+    fn ceil(position: Point2<f32>) -> Point2<i32> {
+        position.map(|v| v.ceil() as i32)
+    }
+
+    fn splat(value: i32) -> Vector2<i32> {
+        Vector2::new(value, value)
+    }
+
+    fn axes() -> &'static [Axis] {
+        &[Axis::X, Axis::Y]
+    }
+
+    fn component(vector: Vector2<i32>, axis: Axis) -> i32 {
+        match axis {
+            Axis::X => vector.x,
+            Axis::Y => vector.y,
+            Axis::Z => panic!("Dim2 doesn't have a Z axis"),
+        }
+    }
+
+    fn with_component(mut vector: Vector2<i32>, axis: Axis, value: i32) -> Vector2<i32> {
+        match axis {
+            Axis::X => vector.x = value,
+            Axis::Y => vector.y = value,
+            Axis::Z => panic!("Dim2 doesn't have a Z axis"),
+        }
+        vector
+    }
+
+    fn min(a: Point2<i32>, b: Point2<i32>) -> Point2<i32> {
+        Point2::new(a.x.min(b.x), a.y.min(b.y))
+    }
+
+    fn max(a: Point2<i32>, b: Point2<i32>) -> Point2<i32> {
+        Point2::new(a.x.max(b.x), a.y.max(b.y))
+    }
+
     fn area(size: Vector2<i32>) -> usize {
-        (size.x * size.y) as usize
+        (size.x as usize).saturating_mul(size.y as usize)
     }
 
     fn is_in_bounds(position: Point2<i32>, size: Vector2<i32>) -> bool {
@@ -44,6 +209,11 @@ impl Dim for Dim2 {
         (position.x + (position.y * size.x)) as usize
     }
 
+    fn position(index: usize, size: Vector2<i32>) -> Point2<i32> {
+        let index = index as i32;
+        Point2::new(index % size.x, index / size.x)
+    }
+
     fn next(
         mut position: Point2<i32>, start: Self::Point, end: Self::Point
     ) -> Option<Point2<i32>> {
@@ -61,6 +231,43 @@ impl Dim for Dim2 {
             Some(position)
         }
     }
+
+    fn to_chunk(position: Point2<i32>, chunk_size: Vector2<i32>) -> (Point2<i32>, Point2<i32>) {
+        let chunk = Point2::new(
+            position.x.div_euclid(chunk_size.x),
+            position.y.div_euclid(chunk_size.y),
+        );
+        let local = Point2::new(
+            position.x.rem_euclid(chunk_size.x),
+            position.y.rem_euclid(chunk_size.y),
+        );
+        (chunk, local)
+    }
+
+    fn from_chunk(
+        chunk: Point2<i32>, local: Point2<i32>, chunk_size: Vector2<i32>
+    ) -> Point2<i32> {
+        Point2::new(
+            chunk.x * chunk_size.x + local.x,
+            chunk.y * chunk_size.y + local.y,
+        )
+    }
+
+    fn neighbor_offsets(connectivity: Connectivity) -> Vec<Vector2<i32>> {
+        let mut offsets = Vec::new();
+        for y in -1..2 {
+            for x in -1..2 {
+                if connectivity.allows(&[x, y]) {
+                    offsets.push(Vector2::new(x, y));
+                }
+            }
+        }
+        offsets
+    }
+
+    fn distance(a: Point2<i32>, b: Point2<i32>, connectivity: Connectivity) -> i32 {
+        connectivity.steps(&[b.x - a.x, b.y - a.y])
+    }
 }
 
 pub enum Dim3 {}
@@ -68,6 +275,7 @@ pub enum Dim3 {}
 impl Dim for Dim3 {
     type Vector = Vector3<i32>;
     type Point = Point3<i32>;
+    type FloatPoint = Point3<f32>;
 
     fn start() -> Self::Point {
         Point3::new(0, 0, 0)
@@ -77,8 +285,49 @@ impl Dim for Dim3 {
         Point3::new(size.x - 1, size.y - 1, size.z - 1)
     }
 
+    fn floor(position: Point3<f32>) -> Point3<i32> {
+        position.map(|v| v.floor() as i32)
+    }
+
+    fn ceil(position: Point3<f32>) -> Point3<i32> {
+        position.map(|v| v.ceil() as i32)
+    }
+
+    fn splat(value: i32) -> Vector3<i32> {
+        Vector3::new(value, value, value)
+    }
+
+    fn axes() -> &'static [Axis] {
+        &[Axis::X, Axis::Y, Axis::Z]
+    }
+
+    fn component(vector: Vector3<i32>, axis: Axis) -> i32 {
+        match axis {
+            Axis::X => vector.x,
+            Axis::Y => vector.y,
+            Axis::Z => vector.z,
+        }
+    }
+
+    fn with_component(mut vector: Vector3<i32>, axis: Axis, value: i32) -> Vector3<i32> {
+        match axis {
+            Axis::X => vector.x = value,
+            Axis::Y => vector.y = value,
+            Axis::Z => vector.z = value,
+        }
+        vector
+    }
+
+    fn min(a: Point3<i32>, b: Point3<i32>) -> Point3<i32> {
+        Point3::new(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))
+    }
+
+    fn max(a: Point3<i32>, b: Point3<i32>) -> Point3<i32> {
+        Point3::new(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
+    }
+
     fn area(size: Vector3<i32>) -> usize {
-        (size.x * size.y * size.z) as usize
+        (size.x as usize).saturating_mul(size.y as usize).saturating_mul(size.z as usize)
     }
 
     fn is_in_bounds(position: Point3<i32>, size: Vector3<i32>) -> bool {
@@ -90,6 +339,12 @@ impl Dim for Dim3 {
         (position.x + (position.y * size.x) + (position.z * size.x * size.y)) as usize
     }
 
+    fn position(index: usize, size: Vector3<i32>) -> Point3<i32> {
+        let index = index as i32;
+        let layer = size.x * size.y;
+        Point3::new(index % size.x, (index % layer) / size.x, index / layer)
+    }
+
     fn next(
         mut position: Point3<i32>, start: Self::Point, end: Self::Point
     ) -> Option<Point3<i32>> {
@@ -112,4 +367,46 @@ impl Dim for Dim3 {
             Some(position)
         }
     }
+
+    fn to_chunk(position: Point3<i32>, chunk_size: Vector3<i32>) -> (Point3<i32>, Point3<i32>) {
+        let chunk = Point3::new(
+            position.x.div_euclid(chunk_size.x),
+            position.y.div_euclid(chunk_size.y),
+            position.z.div_euclid(chunk_size.z),
+        );
+        let local = Point3::new(
+            position.x.rem_euclid(chunk_size.x),
+            position.y.rem_euclid(chunk_size.y),
+            position.z.rem_euclid(chunk_size.z),
+        );
+        (chunk, local)
+    }
+
+    fn from_chunk(
+        chunk: Point3<i32>, local: Point3<i32>, chunk_size: Vector3<i32>
+    ) -> Point3<i32> {
+        Point3::new(
+            chunk.x * chunk_size.x + local.x,
+            chunk.y * chunk_size.y + local.y,
+            chunk.z * chunk_size.z + local.z,
+        )
+    }
+
+    fn neighbor_offsets(connectivity: Connectivity) -> Vec<Vector3<i32>> {
+        let mut offsets = Vec::new();
+        for z in -1..2 {
+            for y in -1..2 {
+                for x in -1..2 {
+                    if connectivity.allows(&[x, y, z]) {
+                        offsets.push(Vector3::new(x, y, z));
+                    }
+                }
+            }
+        }
+        offsets
+    }
+
+    fn distance(a: Point3<i32>, b: Point3<i32>, connectivity: Connectivity) -> i32 {
+        connectivity.steps(&[b.x - a.x, b.y - a.y, b.z - a.z])
+    }
 }
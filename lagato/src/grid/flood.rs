@@ -0,0 +1,144 @@
+use {
+    grid::{Dim, Grid, Connectivity},
+};
+
+impl<Cell, D: Dim, S> Grid<Cell, D, S> {
+    /// Finds all positions connected to `start` for which `predicate` returns true, including
+    /// `start` itself. If `start` doesn't match or is out of bounds, nothing is returned.
+    pub fn flood_fill<F: FnMut(D::Point) -> bool>(
+        &self, start: D::Point, connectivity: Connectivity, mut predicate: F,
+    ) -> Vec<D::Point> {
+        let mut visited: Grid<bool, D> = Grid::empty(self.size());
+        self.flood_from(start, connectivity, &mut predicate, &mut visited)
+    }
+
+    /// Splits all positions for which `predicate` returns true into groups of connected
+    /// positions.
+    pub fn connected_components<F: FnMut(D::Point) -> bool>(
+        &self, connectivity: Connectivity, mut predicate: F,
+    ) -> Vec<Vec<D::Point>> {
+        let mut visited: Grid<bool, D> = Grid::empty(self.size());
+        let mut components = Vec::new();
+
+        for position in self.iter_pos() {
+            if *visited.get(position).unwrap() { continue }
+
+            let component = self.flood_from(position, connectivity, &mut predicate, &mut visited);
+            if !component.is_empty() {
+                components.push(component);
+            }
+        }
+
+        components
+    }
+
+    /// Finds groups of connected positions for which `predicate` returns true, that don't touch
+    /// the edge of the grid. For example, with a predicate matching empty cells, this finds
+    /// rooms that are fully enclosed by walls.
+    pub fn enclosed_regions<F: FnMut(D::Point) -> bool>(
+        &self, connectivity: Connectivity, predicate: F,
+    ) -> Vec<Vec<D::Point>> {
+        let size = self.size();
+        let on_edge = |position| {
+            D::neighbors(position, Connectivity::Orthogonal)
+                .any(|n| !D::is_in_bounds(n, size))
+        };
+
+        let mut regions = self.connected_components(connectivity, predicate);
+        regions.retain(|region| !region.iter().any(|p| on_edge(*p)));
+        regions
+    }
+
+    fn flood_from<F: FnMut(D::Point) -> bool>(
+        &self, start: D::Point, connectivity: Connectivity, predicate: &mut F,
+        visited: &mut Grid<bool, D>,
+    ) -> Vec<D::Point> {
+        match visited.get_mut(start) {
+            Ok(visited) if !*visited => *visited = true,
+            _ => return Vec::new(),
+        }
+        if !predicate(start) {
+            return Vec::new()
+        }
+
+        let offsets = D::neighbor_offsets(connectivity);
+        let mut found = vec![start];
+        let mut open = vec![start];
+
+        while let Some(position) = open.pop() {
+            for offset in &offsets {
+                let neighbor = position + *offset;
+
+                // Only check every position once, whether it matches or not
+                match visited.get_mut(neighbor) {
+                    Ok(visited) if !*visited => *visited = true,
+                    _ => continue,
+                }
+
+                if predicate(neighbor) {
+                    found.push(neighbor);
+                    open.push(neighbor);
+                }
+            }
+        }
+
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Vector2},
+
+        grid::{Grid, Tiles, Range, Connectivity},
+    };
+
+    fn room() -> Tiles<bool> {
+        // A hollow square of walls in the corner of a larger map
+        let mut tiles: Tiles<bool> = Grid::empty(Vector2::new(8, 8));
+        tiles.fill(Range::new_dim2(1, 1, 5, 5), true);
+        tiles.fill(Range::new_dim2(2, 2, 4, 4), false);
+        tiles
+    }
+
+    #[test]
+    fn flood_fill_stays_within_walls() {
+        let tiles = room();
+
+        let inside = tiles.flood_fill(
+            Point2::new(3, 3), Connectivity::All, |p| !*tiles.get(p).unwrap()
+        );
+        assert_eq!(inside.len(), 9);
+
+        let wall = tiles.flood_fill(
+            Point2::new(3, 3), Connectivity::All, |p| *tiles.get(p).unwrap()
+        );
+        assert!(wall.is_empty());
+    }
+
+    #[test]
+    fn enclosed_regions_only_finds_regions_away_from_edges() {
+        let tiles = room();
+
+        let components = tiles.connected_components(
+            Connectivity::Orthogonal, |p| !*tiles.get(p).unwrap()
+        );
+        assert_eq!(components.len(), 2);
+
+        let enclosed = tiles.enclosed_regions(
+            Connectivity::Orthogonal, |p| !*tiles.get(p).unwrap()
+        );
+        assert_eq!(enclosed.len(), 1);
+        assert_eq!(enclosed[0].len(), 9);
+    }
+
+    #[test]
+    fn grid_neighbors_are_bounds_checked() {
+        let tiles: Tiles<bool> = Grid::empty(Vector2::new(3, 3));
+
+        assert_eq!(tiles.neighbors(Point2::new(0, 0), Connectivity::Orthogonal).count(), 2);
+        assert_eq!(tiles.neighbors(Point2::new(1, 1), Connectivity::Orthogonal).count(), 4);
+        assert_eq!(tiles.neighbors(Point2::new(0, 1), Connectivity::All).count(), 5);
+    }
+}
@@ -1,17 +1,29 @@
 use {
-    cgmath::{Point2},
+    std::{
+        marker::{PhantomData},
+        mem,
+    },
 
-    grid::{Dim, Dim2, Range, IterRange},
+    grid::{Dim, Range, IterRange, Storage, Connectivity},
+    grid::changes::{ChangeTracker},
 };
 
+/// A fixed-size grid of cells. By default cells are stored in a `Vec`, which lets them be
+/// accessed by reference. Other `Storage` types can be used to store cells more compactly, in
+/// which case they're accessed by value through `value` and `set`.
 #[derive(Deserialize, Serialize)]
-pub struct Grid<Cell, D: Dim> {
-    cells: Vec<Cell>,
+pub struct Grid<Cell, D: Dim, S = Vec<Cell>> {
+    cells: S,
     size: D::Vector,
+    #[serde(skip)]
+    _cell: PhantomData<Cell>,
+    #[serde(skip)]
+    changes: Option<ChangeTracker<D>>,
 }
 
 impl<Cell: Default, D: Dim> Grid<Cell, D> {
     pub fn empty(size: D::Vector) -> Self {
+        assert!(D::is_valid_size(size), "Invalid grid size for this dimension");
         let amount = D::area(size);
         let mut cells = Vec::with_capacity(amount);
         for _ in 0..amount { cells.push(Cell::default()) }
@@ -19,69 +31,204 @@ impl<Cell: Default, D: Dim> Grid<Cell, D> {
         Grid {
             cells,
             size,
+            _cell: PhantomData,
+            changes: None,
         }
     }
 }
 
-impl<Cell, D: Dim> Grid<Cell, D> {
-    pub fn size(&self) -> D::Vector {
-        self.size
+impl<Cell, D: Dim, S: Storage<Cell>> Grid<Cell, D, S> {
+    pub fn filled(size: D::Vector, value: Cell) -> Self {
+        assert!(D::is_valid_size(size), "Invalid grid size for this dimension");
+        Grid {
+            cells: S::filled(D::area(size), value),
+            size,
+            _cell: PhantomData,
+            changes: None,
+        }
     }
 
-    pub fn get(&self, position: D::Point) -> Result<&Cell, Error> {
+    /// Creates a grid from existing storage, which has to have exactly enough cells for the size.
+    pub fn from_storage(size: D::Vector, cells: S) -> Result<Self, Error> {
+        if !D::is_valid_size(size) || cells.len() != D::area(size) {
+            return Err(Error::SizeMismatch)
+        }
+
+        Ok(Grid {
+            cells,
+            size,
+            _cell: PhantomData,
+            changes: None,
+        })
+    }
+
+    pub fn storage(&self) -> &S {
+        &self.cells
+    }
+
+    pub fn into_storage(self) -> S {
+        self.cells
+    }
+
+    pub fn value(&self, position: D::Point) -> Result<Cell, Error> {
         if D::is_in_bounds(position, self.size) {
-            Ok(&self.cells[D::index(position, self.size)])
+            Ok(self.cells.get(D::index(position, self.size)))
         } else {
             Err(Error::OutOfBounds)
         }
     }
 
-    pub fn get_mut(&mut self, position: D::Point) -> Result<&mut Cell, Error> {
+    pub fn set(&mut self, position: D::Point, value: Cell) -> Result<(), Error> {
         if D::is_in_bounds(position, self.size) {
             let index = D::index(position, self.size);
-            Ok(&mut self.cells[index])
+            self.cells.set(index, value);
+            self.record_change(position);
+            Ok(())
         } else {
             Err(Error::OutOfBounds)
         }
     }
 
+    /// Moves every cell to a new position in place, resizing the grid to `size`. The new size
+    /// must have the same area, and `target` must map every position to a unique new position.
+    pub(crate) fn permute<F: FnMut(D::Point) -> D::Point>(
+        &mut self, size: D::Vector, mut target: F
+    ) {
+        assert_eq!(D::area(size), D::area(self.size));
+
+        let mut targets = vec![0; self.cells.len()];
+        for position in self.iter_pos() {
+            targets[D::index(position, self.size)] = D::index(target(position), size);
+        }
+        self.size = size;
+        let range = self.range();
+        self.record_changes(range);
+
+        // Follow every cycle of the permutation, carrying each cell along to its target and
+        // marking indices as done by pointing them at themselves
+        for start in 0..targets.len() {
+            let mut index = targets[start];
+            if index == start { continue }
+
+            let mut value = self.cells.get(start);
+            targets[start] = start;
+            while index != start {
+                let displaced = self.cells.get(index);
+                self.cells.set(index, value);
+                value = displaced;
+
+                index = mem::replace(&mut targets[index], index);
+            }
+            self.cells.set(start, value);
+        }
+    }
+}
+
+impl<Cell, D: Dim, S> Grid<Cell, D, S> {
+    pub fn size(&self) -> D::Vector {
+        self.size
+    }
+
     pub fn is_in_bounds(&self, position: D::Point) -> bool {
         D::is_in_bounds(position, self.size)
     }
 
-    pub fn iter_pos(&self) -> IterRange<D> {
+    /// The range covering every position in this grid.
+    pub fn range(&self) -> Range<D> {
         Range {
             start: D::start(),
             end: D::end(self.size),
-        }.iter()
+        }
     }
-}
 
-impl<Cell> Grid<Cell, Dim2> {
-    pub fn bounds(&self, start: Point2<f32>, end: Point2<f32>) -> Range<Dim2> {
-        let start_x = (start.x.floor() as i32).max(0);
-        let start_y = (start.y.floor() as i32).max(0);
-        let end_x = (end.x.ceil() as i32).min(self.size.x);
-        let end_y = (end.y.ceil() as i32).min(self.size.y);
+    pub fn iter_pos(&self) -> IterRange<D> {
+        self.range().iter()
+    }
 
+    /// The range of cells touched by an area in continuous space, limited to this grid. Like
+    /// every `Range` the end is inclusive, so an area reaching past the grid ends at the last
+    /// cell, `size - 1`, not at `size`.
+    pub fn bounds(&self, start: D::FloatPoint, end: D::FloatPoint) -> Range<D> {
         Range {
-            start: Point2::new(start_x, start_y),
-            end: Point2::new(end_x, end_y),
+            start: D::max(D::floor(start), D::start()),
+            end: D::min(D::ceil(end), D::end(self.size)),
+        }
+    }
+
+    pub(crate) fn change_tracker(&self) -> &Option<ChangeTracker<D>> {
+        &self.changes
+    }
+
+    pub(crate) fn change_tracker_mut(&mut self) -> &mut Option<ChangeTracker<D>> {
+        &mut self.changes
+    }
+
+    fn record_change(&mut self, position: D::Point) {
+        if let Some(ref mut changes) = self.changes {
+            changes.record(position);
+        }
+    }
+
+    /// Records every position in a range as changed, if changes are being tracked.
+    pub(crate) fn record_changes(&mut self, range: Range<D>) {
+        if let Some(ref mut changes) = self.changes {
+            for position in range.iter() {
+                changes.record(position);
+            }
         }
     }
+
+    /// Iterates over the neighbors of a position that are within this grid.
+    pub fn neighbors(
+        &self, position: D::Point, connectivity: Connectivity
+    ) -> impl Iterator<Item=D::Point> {
+        let size = self.size;
+        D::neighbors(position, connectivity).filter(move |n| D::is_in_bounds(*n, size))
+    }
+}
+
+impl<Cell, D: Dim> Grid<Cell, D> {
+    pub fn get(&self, position: D::Point) -> Result<&Cell, Error> {
+        if D::is_in_bounds(position, self.size) {
+            Ok(&self.cells[D::index(position, self.size)])
+        } else {
+            Err(Error::OutOfBounds)
+        }
+    }
+
+    pub fn get_mut(&mut self, position: D::Point) -> Result<&mut Cell, Error> {
+        if D::is_in_bounds(position, self.size) {
+            let index = D::index(position, self.size);
+            self.record_change(position);
+            Ok(&mut self.cells[index])
+        } else {
+            Err(Error::OutOfBounds)
+        }
+    }
+
+    /// All cells in the order of `Dim::index`.
+    pub(crate) fn cells(&self) -> &[Cell] {
+        &self.cells
+    }
+
+    /// All cells in the order of `Dim::index`, without recording any changes.
+    pub(crate) fn cells_mut(&mut self) -> &mut [Cell] {
+        &mut self.cells
+    }
 }
 
 #[derive(Debug, PartialEq)]
 pub enum Error {
     OutOfBounds,
+    SizeMismatch,
 }
 
 #[cfg(test)]
 mod tests {
     use {
-        cgmath::{Vector2, Vector3},
+        cgmath::{Point2, Vector2, Vector3},
 
-        grid::{Grid, Dim2, Dim3},
+        grid::{Grid, Dim2, Dim3, DimHex, Range, BitStorage, PaletteStorage, Error},
     };
 
     #[test]
@@ -129,4 +276,45 @@ mod tests {
         assert!(got_max);
         assert_eq!(amount_iterated, 10 * 10 * 10);
     }
+
+    #[test]
+    fn compact_storages_behave_like_vec() {
+        let size = Vector3::new(5, 6, 7);
+        let mut vec: Grid<bool, Dim3> = Grid::empty(size);
+        let mut bits: Grid<bool, Dim3, BitStorage> = Grid::filled(size, false);
+        let mut palette: Grid<bool, Dim3, PaletteStorage<bool>> = Grid::filled(size, false);
+
+        for pos in vec.iter_pos() {
+            let value = (pos.x + pos.y * 3 + pos.z * 7) % 4 == 0;
+            *vec.get_mut(pos).unwrap() = value;
+            bits.set(pos, value).unwrap();
+            palette.set(pos, value).unwrap();
+        }
+
+        for pos in vec.iter_pos() {
+            assert_eq!(vec.value(pos), bits.value(pos));
+            assert_eq!(vec.value(pos), palette.value(pos));
+        }
+    }
+
+    #[test]
+    fn bounds_are_inclusive_and_clamped() {
+        let grid: Grid<u8, Dim2> = Grid::empty(Vector2::new(4, 4));
+
+        let bounds = grid.bounds(Point2::new(-1.5, 0.5), Point2::new(9.5, 1.2));
+        assert_eq!(bounds, Range::new_dim2(0, 0, 3, 2));
+        assert!(grid.is_in_bounds(bounds.end));
+        assert_eq!(grid.bounds(Point2::new(0.0, 0.0), Point2::new(4.0, 4.0)), grid.range());
+    }
+
+    #[test]
+    #[should_panic(expected = "Invalid grid size")]
+    fn negative_sizes_are_rejected() {
+        let result: Result<Grid<u8, Dim3>, _> = Grid::from_storage(Vector3::new(2, -1, 2), vec![]);
+        assert_eq!(result.err(), Some(Error::SizeMismatch));
+        let result: Result<Grid<u8, DimHex>, _> = Grid::from_storage(Vector2::new(-2, 0), vec![]);
+        assert_eq!(result.err(), Some(Error::SizeMismatch));
+
+        let _: Grid<u8, Dim2> = Grid::empty(Vector2::new(-1, 4));
+    }
 }
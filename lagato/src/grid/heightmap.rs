@@ -0,0 +1,353 @@
+use {
+    cgmath::{Point2, Vector2, Vector3, InnerSpace},
+
+    grid::{Grid, Dim, Dim2, Voxels, Range, Connectivity, Error},
+    noise::{split_mix},
+};
+
+/// Settings for simulating rain drops carving out valleys in a heightmap. Every drop rolls
+/// downhill, picking up sediment while speeding up and depositing it again when slowing down or
+/// evaporating.
+#[derive(Clone, Copy, Debug)]
+pub struct HydraulicErosion {
+    pub droplets: usize,
+    /// How many steps a drop takes at most before it's gone.
+    pub lifetime: usize,
+    /// How much a drop keeps going in the direction it was going, rather than downhill, from 0
+    /// to 1.
+    pub inertia: f32,
+    /// How much sediment a drop can carry, relative to its speed, water and the slope.
+    pub capacity: f32,
+    /// The slope used for the capacity on flat ground, so drops keep carrying some sediment.
+    pub min_slope: f32,
+    /// How much of its remaining capacity a drop picks up every step, from 0 to 1.
+    pub erosion: f32,
+    /// How much of its excess sediment a drop deposits every step, from 0 to 1.
+    pub deposition: f32,
+    /// How much water evaporates every step, from 0 to 1.
+    pub evaporation: f32,
+    pub gravity: f32,
+}
+
+impl HydraulicErosion {
+    pub fn new(droplets: usize) -> Self {
+        HydraulicErosion {
+            droplets,
+            lifetime: 30,
+            inertia: 0.05,
+            capacity: 4.0,
+            min_slope: 0.01,
+            erosion: 0.3,
+            deposition: 0.3,
+            evaporation: 0.01,
+            gravity: 4.0,
+        }
+    }
+}
+
+impl Grid<f32, Dim2> {
+    /// Rescales all heights so they range from 0 to 1. A completely flat heightmap becomes 0
+    /// everywhere.
+    pub fn normalize(&mut self) {
+        let min = self.iter().cloned().fold(f32::INFINITY, f32::min);
+        let max = self.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let scale = if max > min { 1.0 / (max - min) } else { 0.0 };
+
+        for height in self.iter_mut() {
+            *height = (*height - min) * scale;
+        }
+    }
+
+    /// Blurs the heightmap by averaging every height with its 8 neighbors, `iterations` times.
+    pub fn smooth(&mut self, iterations: usize) {
+        let size = self.size();
+        let offsets = Dim2::neighbor_offsets(Connectivity::All);
+        let mut previous = self.cells().to_vec();
+
+        for _ in 0..iterations {
+            previous.copy_from_slice(self.cells());
+            for (index, height) in self.iter_mut().enumerate() {
+                let position = Dim2::position(index, size);
+                let mut total = previous[index];
+                let mut count = 1.0;
+
+                for offset in &offsets {
+                    let neighbor = position + offset;
+                    if Dim2::is_in_bounds(neighbor, size) {
+                        total += previous[Dim2::index(neighbor, size)];
+                        count += 1.0;
+                    }
+                }
+
+                *height = total / count;
+            }
+        }
+    }
+
+    /// Simulates material sliding down slopes steeper than `talus`, the height difference
+    /// between neighbors where material stops sliding. `rate` is how much of the excess moves
+    /// every iteration, from 0 to 1. The total amount of material stays the same.
+    pub fn erode_thermal(&mut self, iterations: usize, talus: f32, rate: f32) {
+        let size = self.size();
+        let offsets = Dim2::neighbor_offsets(Connectivity::All);
+        let mut deltas = vec![0.0; self.cells().len()];
+
+        for _ in 0..iterations {
+            deltas.fill(0.0);
+
+            let cells = self.cells();
+            for (index, height) in cells.iter().enumerate() {
+                let position = Dim2::position(index, size);
+                let lower = |offset: &Vector2<i32>| {
+                    let neighbor = position + offset;
+                    if !Dim2::is_in_bounds(neighbor, size) {
+                        return None
+                    }
+
+                    let neighbor_index = Dim2::index(neighbor, size);
+                    let difference = height - cells[neighbor_index];
+                    if difference > talus { Some((neighbor_index, difference)) } else { None }
+                };
+
+                let total: f32 = offsets.iter().filter_map(&lower).map(|(_, d)| d).sum();
+                let steepest = offsets.iter().filter_map(&lower).map(|(_, d)| d)
+                    .fold(0.0, f32::max);
+                if total == 0.0 {
+                    continue
+                }
+
+                // Move material to every lower neighbor, more of it to the steepest ones
+                let moved = rate * (steepest - talus) * 0.5;
+                deltas[index] -= moved;
+                for (neighbor_index, difference) in offsets.iter().filter_map(&lower) {
+                    deltas[neighbor_index] += moved * difference / total;
+                }
+            }
+
+            for (height, delta) in self.iter_mut().zip(&deltas) {
+                *height += delta;
+            }
+        }
+    }
+
+    /// Simulates rain eroding the heightmap. Drops start at random positions, picked using
+    /// `seed`. Sediment carried off the edges of the heightmap is lost.
+    pub fn erode_hydraulic(&mut self, settings: &HydraulicErosion, seed: u64) {
+        let size = self.size();
+        if size.x < 2 || size.y < 2 {
+            return
+        }
+        let range = self.range();
+        self.record_changes(range);
+
+        let mut state = seed;
+        let mut random = |max: i32| {
+            (split_mix(&mut state) >> 40) as f32 / (1 << 24) as f32 * (max - 1) as f32
+        };
+
+        for _ in 0..settings.droplets {
+            let mut position = Point2::new(random(size.x), random(size.y));
+            let mut direction = Vector2::new(0.0, 0.0);
+            let mut speed = 1.0;
+            let mut water = 1.0;
+            let mut sediment = 0.0;
+
+            for _ in 0..settings.lifetime {
+                let (height, gradient) = self.sample(position);
+
+                direction = direction * settings.inertia - gradient * (1.0 - settings.inertia);
+                if direction.magnitude2() == 0.0 {
+                    break
+                }
+                direction = direction.normalize();
+
+                let old_position = position;
+                position += direction;
+                if position.x < 0.0 || position.y < 0.0
+                    || position.x >= (size.x - 1) as f32 || position.y >= (size.y - 1) as f32 {
+                    break
+                }
+
+                let height_difference = self.sample(position).0 - height;
+                let capacity = (-height_difference).max(settings.min_slope)
+                    * speed * water * settings.capacity;
+
+                if sediment > capacity || height_difference > 0.0 {
+                    // Going uphill fills up the pit behind the drop, otherwise drop the excess
+                    let amount = if height_difference > 0.0 {
+                        height_difference.min(sediment)
+                    } else {
+                        (sediment - capacity) * settings.deposition
+                    };
+                    sediment -= amount;
+                    self.spread(old_position, amount);
+                } else {
+                    // Never dig deeper than the height difference, that would leave holes
+                    let amount = ((capacity - sediment) * settings.erosion)
+                        .min(-height_difference);
+                    sediment += amount;
+                    self.spread(old_position, -amount);
+                }
+
+                speed = (speed * speed - height_difference * settings.gravity).max(0.0).sqrt();
+                water *= 1.0 - settings.evaporation;
+            }
+        }
+    }
+
+    /// The gradient of the heightmap at a position, pointing uphill. Uses the closest heights
+    /// at the edges.
+    pub fn slope(&self, position: Point2<i32>) -> Result<Vector2<f32>, Error> {
+        if !self.is_in_bounds(position) {
+            return Err(Error::OutOfBounds)
+        }
+
+        let end = Dim2::end(self.size());
+        let height = |x: i32, y: i32| *self.get(Point2::new(x, y)).unwrap();
+
+        // Central differences, falling back to one-sided differences at the edges
+        let (left, right) = ((position.x - 1).max(0), (position.x + 1).min(end.x));
+        let (top, bottom) = ((position.y - 1).max(0), (position.y + 1).min(end.y));
+        let dx = (height(right, position.y) - height(left, position.y))
+            / (right - left).max(1) as f32;
+        let dy = (height(position.x, bottom) - height(position.x, top))
+            / (bottom - top).max(1) as f32;
+        Ok(Vector2::new(dx, dy))
+    }
+
+    /// The surface normal at a position, in the same space as `to_voxels` with heights
+    /// multiplied by `height_scale` along Y.
+    pub fn normal(
+        &self, position: Point2<i32>, height_scale: f32,
+    ) -> Result<Vector3<f32>, Error> {
+        let slope = self.slope(position)? * height_scale;
+        Ok(Vector3::new(-slope.x, 1.0, -slope.y).normalize())
+    }
+
+    /// Converts the heightmap into columns of voxels, with a height of 1 filling the entire
+    /// height of the voxels. The heightmap's Y axis becomes the Z axis of the voxels. Every
+    /// voxel in a column gets its material from its depth, 0 being the top voxel, voxels above
+    /// the column stay empty.
+    pub fn to_voxels<T: Default + Clone, F: FnMut(i32) -> T>(
+        &self, height: i32, mut material: F,
+    ) -> Voxels<T> {
+        let size = self.size();
+        let mut voxels = Grid::empty(Vector3::new(size.x, height, size.y));
+
+        for (position, column_height) in self.enumerate() {
+            let column_height = ((column_height * height as f32).round() as i32).min(height);
+            let column = Range::new_dim3(
+                position.x, 0, position.y,
+                position.x, column_height - 1, position.y,
+            );
+            for voxel in column.iter() {
+                *voxels.get_mut(voxel).unwrap() = material(column_height - 1 - voxel.y);
+            }
+        }
+
+        voxels
+    }
+
+    /// Interpolates the height and gradient at a continuous position, which must be at least
+    /// one cell away from the end.
+    fn sample(&self, position: Point2<f32>) -> (f32, Vector2<f32>) {
+        let cell = Dim2::floor(position);
+        let (u, v) = (position.x - cell.x as f32, position.y - cell.y as f32);
+        let height = |x: i32, y: i32| *self.get(cell + Vector2::new(x, y)).unwrap();
+        let (h00, h10, h01, h11) = (height(0, 0), height(1, 0), height(0, 1), height(1, 1));
+
+        let interpolated = h00 * (1.0 - u) * (1.0 - v) + h10 * u * (1.0 - v)
+            + h01 * (1.0 - u) * v + h11 * u * v;
+        let gradient = Vector2::new(
+            (h10 - h00) * (1.0 - v) + (h11 - h01) * v,
+            (h01 - h00) * (1.0 - u) + (h11 - h10) * u,
+        );
+        (interpolated, gradient)
+    }
+
+    /// Adds an amount to the 4 cells around a continuous position, weighted by how close they
+    /// are.
+    fn spread(&mut self, position: Point2<f32>, amount: f32) {
+        let cell = Dim2::floor(position);
+        let (u, v) = (position.x - cell.x as f32, position.y - cell.y as f32);
+        let weights = [(0, 0, (1.0 - u) * (1.0 - v)), (1, 0, u * (1.0 - v)),
+            (0, 1, (1.0 - u) * v), (1, 1, u * v)];
+
+        let size = self.size();
+        let cells = self.cells_mut();
+        for &(x, y, weight) in &weights {
+            cells[Dim2::index(cell + Vector2::new(x, y), size)] += amount * weight;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Point3, Vector2},
+
+        grid::{Grid, Tiles, HydraulicErosion, Error},
+    };
+
+    fn ramp() -> Tiles<f32> {
+        let mut tiles: Tiles<f32> = Grid::empty(Vector2::new(16, 16));
+        for (position, height) in tiles.enumerate_mut() {
+            *height = position.x as f32 * 2.0 + 3.0;
+        }
+        tiles
+    }
+
+    #[test]
+    fn normalize_smooth_and_slope() {
+        let mut tiles = ramp();
+        tiles.normalize();
+        assert_eq!(tiles.get(Point2::new(0, 3)), Ok(&0.0));
+        assert_eq!(tiles.get(Point2::new(15, 3)), Ok(&1.0));
+        assert!((tiles.slope(Point2::new(4, 4)).unwrap().x - 1.0 / 15.0).abs() < 1e-6);
+        assert!((tiles.slope(Point2::new(15, 0)).unwrap().x - 1.0 / 15.0).abs() < 1e-6);
+        assert_eq!(tiles.slope(Point2::new(4, 4)).unwrap().y, 0.0);
+        assert_eq!(tiles.slope(Point2::new(16, 0)), Err(Error::OutOfBounds));
+        assert_eq!(tiles.normal(Point2::new(-1, 0), 1.0), Err(Error::OutOfBounds));
+
+        let normal = tiles.normal(Point2::new(4, 4), 15.0).unwrap();
+        assert!((normal.x + 0.5f32.sqrt()).abs() < 1e-5);
+        assert!((normal.y - 0.5f32.sqrt()).abs() < 1e-5);
+
+        let mut spike: Tiles<f32> = Grid::empty(Vector2::new(5, 5));
+        *spike.get_mut(Point2::new(2, 2)).unwrap() = 9.0;
+        spike.smooth(1);
+        assert_eq!(spike.get(Point2::new(2, 2)), Ok(&1.0));
+        assert_eq!(spike.get(Point2::new(0, 0)), Ok(&0.0));
+    }
+
+    #[test]
+    fn erosion_flattens_terrain() {
+        let mut spike: Tiles<f32> = Grid::empty(Vector2::new(9, 9));
+        *spike.get_mut(Point2::new(4, 4)).unwrap() = 10.0;
+        spike.erode_thermal(50, 0.5, 0.5);
+        let total: f32 = spike.iter().sum();
+        let highest = spike.iter().cloned().fold(0.0, f32::max);
+        assert!((total - 10.0).abs() < 1e-3);
+        assert!(highest < 3.0);
+
+        let mut eroded = ramp();
+        let mut again = ramp();
+        eroded.erode_hydraulic(&HydraulicErosion::new(200), 3);
+        again.erode_hydraulic(&HydraulicErosion::new(200), 3);
+        assert!(eroded.iter().eq(again.iter()));
+        assert!(eroded.iter().zip(ramp().iter()).any(|(a, b)| a != b));
+        assert!(eroded.iter().all(|h| h.is_finite()));
+    }
+
+    #[test]
+    fn voxel_columns_get_materials_by_depth() {
+        let mut tiles: Tiles<f32> = Grid::empty(Vector2::new(2, 2));
+        *tiles.get_mut(Point2::new(1, 0)).unwrap() = 0.5;
+
+        let voxels = tiles.to_voxels(8, |depth| if depth == 0 { 1 } else { 2 });
+        assert_eq!(voxels.get(Point3::new(1, 3, 0)), Ok(&1));
+        assert_eq!(voxels.get(Point3::new(1, 0, 0)), Ok(&2));
+        assert_eq!(voxels.get(Point3::new(1, 4, 0)), Ok(&0));
+        assert_eq!(voxels.iter().filter(|v| **v != 0).count(), 4);
+    }
+}
@@ -0,0 +1,394 @@
+use {
+    cgmath::{Vector2, Point2, Point3},
+
+    grid::{Dim, Dim2, Axis, Connectivity},
+};
+
+/// The offsets to the 6 neighbors of a hex, going around counter-clockwise in axial
+/// coordinates.
+const DIRECTIONS: [Vector2<i32>; 6] = [
+    Vector2 { x: 1, y: 0 },
+    Vector2 { x: 1, y: -1 },
+    Vector2 { x: 0, y: -1 },
+    Vector2 { x: -1, y: 0 },
+    Vector2 { x: -1, y: 1 },
+    Vector2 { x: 0, y: 1 },
+];
+
+/// Hexagons, using axial coordinates with Q as X and R as Y. The third cube coordinate S is
+/// always `-q - r`, so it isn't stored.
+///
+/// Grids using this store a rhombus of hexes, with every position from 0 to the size along Q
+/// and R, and ranges are rhombuses the same way. Every hex has 6 neighbors, whichever
+/// connectivity is asked for.
+pub enum DimHex {}
+
+impl DimHex {
+    /// Converts axial coordinates into cube coordinates.
+    pub fn to_cube(position: Point2<i32>) -> Point3<i32> {
+        Point3::new(position.x, position.y, -position.x - position.y)
+    }
+
+    /// Converts cube coordinates into axial coordinates, dropping S.
+    pub fn from_cube(position: Point3<i32>) -> Point2<i32> {
+        Point2::new(position.x, position.y)
+    }
+
+    /// The hex containing a continuous axial position.
+    pub fn round(position: Point2<f32>) -> Point2<i32> {
+        let s = -position.x - position.y;
+        let (mut q, mut r) = (position.x.round(), position.y.round());
+        let rounded_s = s.round();
+
+        // Rounding every coordinate can break q + r + s = 0, so the coordinate that was rounded
+        // the most gets recalculated from the others
+        let (q_diff, r_diff, s_diff) =
+            ((q - position.x).abs(), (r - position.y).abs(), (rounded_s - s).abs());
+        if q_diff > r_diff && q_diff > s_diff {
+            q = -r - rounded_s;
+        } else if r_diff > s_diff {
+            r = -q - rounded_s;
+        }
+
+        Point2::new(q as i32, r as i32)
+    }
+
+    /// Iterates over the hexes exactly `radius` steps away from `center`. A radius of 0 gives
+    /// only the center, a negative radius gives nothing.
+    pub fn ring(center: Point2<i32>, radius: i32) -> HexRing {
+        HexRing {
+            position: center + DIRECTIONS[4] * radius,
+            radius,
+            // Starting past the last side ends the ring right away
+            side: if radius < 0 { DIRECTIONS.len() } else { 0 },
+            step: 0,
+        }
+    }
+
+    /// Iterates over the hexes up to `radius` steps away from `center`, starting at the center
+    /// and going outwards ring by ring. A negative radius gives nothing.
+    pub fn spiral(center: Point2<i32>, radius: i32) -> HexSpiral {
+        HexSpiral {
+            center,
+            radius,
+            ring: DimHex::ring(center, if radius < 0 { -1 } else { 0 }),
+        }
+    }
+}
+
+impl Dim for DimHex {
+    type Vector = Vector2<i32>;
+    type Point = Point2<i32>;
+    type FloatPoint = Point2<f32>;
+
+    fn start() -> Point2<i32> {
+        Dim2::start()
+    }
+
+    fn end(size: Vector2<i32>) -> Point2<i32> {
+        Dim2::end(size)
+    }
+
+    fn floor(position: Point2<f32>) -> Point2<i32> {
+        Dim2::floor(position)
+    }
+
+    fn ceil(position: Point2<f32>) -> Point2<i32> {
+        Dim2::ceil(position)
+    }
+
+    fn splat(value: i32) -> Vector2<i32> {
+        Dim2::splat(value)
+    }
+
+    fn axes() -> &'static [Axis] {
+        Dim2::axes()
+    }
+
+    fn component(vector: Vector2<i32>, axis: Axis) -> i32 {
+        Dim2::component(vector, axis)
+    }
+
+    fn with_component(vector: Vector2<i32>, axis: Axis, value: i32) -> Vector2<i32> {
+        Dim2::with_component(vector, axis, value)
+    }
+
+    fn min(a: Point2<i32>, b: Point2<i32>) -> Point2<i32> {
+        Dim2::min(a, b)
+    }
+
+    fn max(a: Point2<i32>, b: Point2<i32>) -> Point2<i32> {
+        Dim2::max(a, b)
+    }
+
+    fn area(size: Vector2<i32>) -> usize {
+        Dim2::area(size)
+    }
+
+    fn is_in_bounds(position: Point2<i32>, size: Vector2<i32>) -> bool {
+        Dim2::is_in_bounds(position, size)
+    }
+
+    fn index(position: Point2<i32>, size: Vector2<i32>) -> usize {
+        Dim2::index(position, size)
+    }
+
+    fn position(index: usize, size: Vector2<i32>) -> Point2<i32> {
+        Dim2::position(index, size)
+    }
+
+    fn next(position: Point2<i32>, start: Point2<i32>, end: Point2<i32>) -> Option<Point2<i32>> {
+        Dim2::next(position, start, end)
+    }
+
+    fn to_chunk(position: Point2<i32>, chunk_size: Vector2<i32>) -> (Point2<i32>, Point2<i32>) {
+        Dim2::to_chunk(position, chunk_size)
+    }
+
+    fn from_chunk(
+        chunk: Point2<i32>, local: Point2<i32>, chunk_size: Vector2<i32>
+    ) -> Point2<i32> {
+        Dim2::from_chunk(chunk, local, chunk_size)
+    }
+
+    fn neighbor_offsets(_connectivity: Connectivity) -> Vec<Vector2<i32>> {
+        DIRECTIONS.to_vec()
+    }
+
+    fn distance(a: Point2<i32>, b: Point2<i32>, _connectivity: Connectivity) -> i32 {
+        let delta = DimHex::to_cube(b) - DimHex::to_cube(a);
+        (delta.x.abs() + delta.y.abs() + delta.z.abs()) / 2
+    }
+}
+
+/// Iterates over the hexes in a ring around a center, see `DimHex::ring`.
+pub struct HexRing {
+    position: Point2<i32>,
+    radius: i32,
+    side: usize,
+    step: i32,
+}
+
+impl Iterator for HexRing {
+    type Item = Point2<i32>;
+
+    fn next(&mut self) -> Option<Point2<i32>> {
+        if self.side >= DIRECTIONS.len() {
+            return None
+        }
+
+        let position = self.position;
+        if self.radius == 0 {
+            self.side = DIRECTIONS.len();
+            return Some(position)
+        }
+
+        // Walk along the ring's 6 sides, each `radius` steps long
+        self.position += DIRECTIONS[self.side];
+        self.step += 1;
+        if self.step == self.radius {
+            self.step = 0;
+            self.side += 1;
+        }
+
+        Some(position)
+    }
+}
+
+/// Iterates over the hexes around a center ring by ring, see `DimHex::spiral`.
+pub struct HexSpiral {
+    center: Point2<i32>,
+    radius: i32,
+    ring: HexRing,
+}
+
+impl Iterator for HexSpiral {
+    type Item = Point2<i32>;
+
+    fn next(&mut self) -> Option<Point2<i32>> {
+        loop {
+            if let Some(position) = self.ring.next() {
+                return Some(position)
+            }
+
+            let next_radius = self.ring.radius + 1;
+            if next_radius > self.radius {
+                return None
+            }
+            self.ring = DimHex::ring(self.center, next_radius);
+        }
+    }
+}
+
+/// Iterates over the hexes on a straight line between two hexes, including both ends.
+pub struct HexLine {
+    start: Point2<f32>,
+    end: Point2<f32>,
+    steps: i32,
+    step: i32,
+}
+
+impl HexLine {
+    pub fn new(start: Point2<i32>, end: Point2<i32>) -> Self {
+        // Nudging the line slightly keeps it from running exactly along hex edges, where
+        // rounding could go either way
+        let nudge = |p: Point2<i32>| Point2::new(p.x as f32 + 1e-6, p.y as f32 + 2e-6);
+
+        HexLine {
+            start: nudge(start),
+            end: nudge(end),
+            steps: DimHex::distance(start, end, Connectivity::All),
+            step: 0,
+        }
+    }
+}
+
+impl Iterator for HexLine {
+    type Item = Point2<i32>;
+
+    fn next(&mut self) -> Option<Point2<i32>> {
+        if self.step > self.steps {
+            return None
+        }
+
+        let t = if self.steps == 0 { 0.0 } else { self.step as f32 / self.steps as f32 };
+        self.step += 1;
+
+        Some(DimHex::round(self.start + (self.end - self.start) * t))
+    }
+}
+
+/// Which way hexes are drawn, which decides how they tile.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum HexOrientation {
+    /// A corner points up, rows of hexes are horizontal.
+    Pointy,
+    /// A flat side is on top, columns of hexes are vertical.
+    Flat,
+}
+
+/// Converts between hexes and pixels.
+#[derive(Clone, Copy, Debug)]
+pub struct HexLayout {
+    pub orientation: HexOrientation,
+    /// The distance from the center of a hex to its corners, in pixels. Can differ between X
+    /// and Y to squash hexes.
+    pub size: Vector2<f32>,
+    /// The pixel position of the center of hex (0, 0).
+    pub origin: Point2<f32>,
+}
+
+const SQRT_3: f32 = 1.732_050_8;
+
+impl HexLayout {
+    pub fn new(orientation: HexOrientation, size: f32) -> Self {
+        HexLayout {
+            orientation,
+            size: Vector2::new(size, size),
+            origin: Point2::new(0.0, 0.0),
+        }
+    }
+
+    /// The pixel position of the center of a hex.
+    pub fn hex_to_pixel(&self, hex: Point2<i32>) -> Point2<f32> {
+        let (q, r) = (hex.x as f32, hex.y as f32);
+        let (x, y) = match self.orientation {
+            HexOrientation::Pointy => (SQRT_3 * q + SQRT_3 / 2.0 * r, 1.5 * r),
+            HexOrientation::Flat => (1.5 * q, SQRT_3 / 2.0 * q + SQRT_3 * r),
+        };
+
+        Point2::new(x * self.size.x + self.origin.x, y * self.size.y + self.origin.y)
+    }
+
+    /// The continuous axial position at a pixel, use `DimHex::round` to get the hex.
+    pub fn pixel_to_axial(&self, pixel: Point2<f32>) -> Point2<f32> {
+        let x = (pixel.x - self.origin.x) / self.size.x;
+        let y = (pixel.y - self.origin.y) / self.size.y;
+
+        match self.orientation {
+            HexOrientation::Pointy =>
+                Point2::new(SQRT_3 / 3.0 * x - y / 3.0, 2.0 / 3.0 * y),
+            HexOrientation::Flat =>
+                Point2::new(2.0 / 3.0 * x, -x / 3.0 + SQRT_3 / 3.0 * y),
+        }
+    }
+
+    /// The hex containing a pixel.
+    pub fn pixel_to_hex(&self, pixel: Point2<f32>) -> Point2<i32> {
+        DimHex::round(self.pixel_to_axial(pixel))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Vector2},
+
+        grid::{
+            Dim, DimHex, Grid, HexTiles, Range, Connectivity, HexLine, HexLayout,
+            HexOrientation,
+        },
+    };
+
+    #[test]
+    fn hexes_work_with_grids() {
+        let mut tiles: HexTiles<u8> = Grid::empty(Vector2::new(5, 5));
+        let center = Point2::new(2, 2);
+        for position in DimHex::neighbors(center, Connectivity::Orthogonal) {
+            assert_eq!(DimHex::distance(center, position, Connectivity::All), 1);
+            *tiles.get_mut(position).unwrap() = 1;
+        }
+
+        assert_eq!(tiles.iter().filter(|c| **c == 1).count(), 6);
+        assert_eq!(tiles.get(Point2::new(1, 1)), Ok(&0));
+        assert_eq!(tiles.get(Point2::new(3, 1)), Ok(&1));
+        assert_eq!(DimHex::distance(Point2::new(0, 0), Point2::new(3, -5), Connectivity::All), 5);
+
+        tiles.fill(Range::new(Point2::new(0, 0), Point2::new(1, 1)), 2);
+        assert_eq!(tiles.iter().filter(|c| **c == 2).count(), 4);
+    }
+
+    #[test]
+    fn rings_spirals_and_lines() {
+        let center = Point2::new(3, -2);
+        let ring: Vec<_> = DimHex::ring(center, 3).collect();
+        assert_eq!(ring.len(), 18);
+        assert!(ring.iter().all(|p| DimHex::distance(center, *p, Connectivity::All) == 3));
+        assert_eq!(DimHex::ring(center, 0).collect::<Vec<_>>(), vec![center]);
+
+        let spiral: Vec<_> = DimHex::spiral(center, 2).collect();
+        assert_eq!(spiral.len(), 1 + 6 + 12);
+        assert_eq!(spiral[0], center);
+
+        // Negative radii are empty, taking a limited amount keeps this from hanging if not
+        assert_eq!(DimHex::ring(center, -1).take(100).count(), 0);
+        assert_eq!(DimHex::spiral(center, -2).take(100).count(), 0);
+
+        let end = Point2::new(-1, 4);
+        let line: Vec<_> = HexLine::new(center, end).collect();
+        assert_eq!(line.len(), 7);
+        assert_eq!((line[0], line[6]), (center, end));
+        for pair in line.windows(2) {
+            assert_eq!(DimHex::distance(pair[0], pair[1], Connectivity::All), 1);
+        }
+    }
+
+    #[test]
+    fn pixels_convert_to_hexes() {
+        for orientation in &[HexOrientation::Pointy, HexOrientation::Flat] {
+            let mut layout = HexLayout::new(*orientation, 10.0);
+            layout.origin = Point2::new(5.0, -3.0);
+
+            for hex in DimHex::spiral(Point2::new(0, 0), 3) {
+                let pixel = layout.hex_to_pixel(hex);
+                assert_eq!(layout.pixel_to_hex(pixel), hex);
+                assert_eq!(layout.pixel_to_hex(pixel + Vector2::new(4.0, -4.0)), hex);
+            }
+        }
+
+        let pointy = HexLayout::new(HexOrientation::Pointy, 10.0);
+        assert_eq!(pointy.pixel_to_hex(Point2::new(0.0, 14.0)), Point2::new(0, 1));
+        let flat = HexLayout::new(HexOrientation::Flat, 10.0);
+        assert_eq!(flat.pixel_to_hex(Point2::new(14.0, 0.0)), Point2::new(1, 0));
+    }
+}
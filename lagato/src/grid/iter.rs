@@ -0,0 +1,230 @@
+use {
+    std::{slice, vec},
+
+    grid::{Dim, Grid, Range, IterRange},
+};
+
+#[cfg(feature = "parallel")]
+use rayon::{
+    prelude::*,
+    slice::{Iter as ParIter, IterMut as ParIterMut},
+};
+
+impl<Cell, D: Dim> Grid<Cell, D> {
+    /// Iterates over all cells in the order they're stored in, which is the same order as
+    /// `iter_pos` for `Dim2` and `Dim3`.
+    pub fn iter(&self) -> slice::Iter<'_, Cell> {
+        self.cells().iter()
+    }
+
+    /// Iterates mutably over all cells in the order they're stored in. When tracking changes,
+    /// every cell is recorded as changed.
+    pub fn iter_mut(&mut self) -> slice::IterMut<'_, Cell> {
+        let range = self.range();
+        self.record_changes(range);
+        self.cells_mut().iter_mut()
+    }
+
+    /// Iterates over all cells together with their positions.
+    pub fn enumerate(&self) -> impl Iterator<Item=(D::Point, &Cell)> {
+        let size = self.size();
+        self.iter().enumerate().map(move |(index, cell)| (D::position(index, size), cell))
+    }
+
+    /// Iterates mutably over all cells together with their positions. When tracking changes,
+    /// every cell is recorded as changed.
+    pub fn enumerate_mut(&mut self) -> impl Iterator<Item=(D::Point, &mut Cell)> {
+        let size = self.size();
+        self.iter_mut().enumerate().map(move |(index, cell)| (D::position(index, size), cell))
+    }
+
+    /// Iterates over the cells within a range together with their positions. Parts of the range
+    /// outside of the grid are skipped.
+    pub fn enumerate_range(&self, range: Range<D>) -> impl Iterator<Item=(D::Point, &Cell)> {
+        let size = self.size();
+        let cells = self.cells();
+        self.range().intersection(&range).into_iter()
+            .flat_map(|range| range.iter())
+            .map(move |position| (position, &cells[D::index(position, size)]))
+    }
+
+    /// Iterates mutably over the cells within a range together with their positions, in the
+    /// order they're stored in. Parts of the range outside of the grid are skipped. When
+    /// tracking changes, every cell in the range is recorded as changed.
+    pub fn enumerate_range_mut(&mut self, range: Range<D>) -> EnumerateRangeMut<'_, Cell, D> {
+        let size = self.size();
+        let range = self.range().intersection(&range);
+        if let Some(range) = range {
+            self.record_changes(range);
+        }
+
+        // Row by row layouts store the range in the order it's walked in, other layouts need
+        // their indices sorted so we can walk over the cells without going back
+        let mut positions = None;
+        let mut sorted = Vec::new();
+        if D::is_row_major() {
+            positions = range.map(|range| range.iter());
+        } else if let Some(range) = range {
+            sorted.extend(range.iter().map(|position| (D::index(position, size), position)));
+            sorted.sort_by_key(|&(index, _)| index);
+        }
+
+        EnumerateRangeMut {
+            cells: self.cells_mut().iter_mut(),
+            size,
+            positions,
+            sorted: sorted.into_iter(),
+            next_index: 0,
+        }
+    }
+}
+
+#[cfg(feature = "parallel")]
+impl<Cell, D: Dim> Grid<Cell, D> {
+    /// Iterates over all cells on multiple threads.
+    pub fn par_iter(&self) -> ParIter<'_, Cell> where Cell: Sync {
+        self.cells().par_iter()
+    }
+
+    /// Iterates mutably over all cells on multiple threads. When tracking changes, every cell is
+    /// recorded as changed.
+    pub fn par_iter_mut(&mut self) -> ParIterMut<'_, Cell> where Cell: Send {
+        let range = self.range();
+        self.record_changes(range);
+        self.cells_mut().par_iter_mut()
+    }
+
+    /// Iterates over all cells together with their positions on multiple threads.
+    pub fn par_enumerate(
+        &self
+    ) -> impl IndexedParallelIterator<Item=(D::Point, &Cell)> where Cell: Sync {
+        let size = self.size();
+        self.par_iter().enumerate().map(move |(index, cell)| (D::position(index, size), cell))
+    }
+
+    /// Iterates mutably over all cells together with their positions on multiple threads. When
+    /// tracking changes, every cell is recorded as changed.
+    pub fn par_enumerate_mut(
+        &mut self
+    ) -> impl IndexedParallelIterator<Item=(D::Point, &mut Cell)> where Cell: Send {
+        let size = self.size();
+        self.par_iter_mut().enumerate().map(move |(index, cell)| (D::position(index, size), cell))
+    }
+
+    /// Iterates over the cells within a range together with their positions on multiple
+    /// threads. Parts of the range outside of the grid are skipped.
+    pub fn par_enumerate_range(
+        &self, range: Range<D>
+    ) -> impl ParallelIterator<Item=(D::Point, &Cell)> where Cell: Sync {
+        let size = self.size();
+        let cells = self.cells();
+        let range = self.range().intersection(&range);
+        let volume = range.map(|range| range.volume()).unwrap_or(0);
+
+        (0..volume).into_par_iter().map(move |index| {
+            // Split the index into an offset within the range, row by row
+            let range = range.unwrap();
+            let range_size = range.size();
+            let mut remaining = index as i32;
+            let mut offset = range_size;
+            for axis in D::axes() {
+                let length = D::component(range_size, *axis);
+                offset = D::with_component(offset, *axis, remaining % length);
+                remaining /= length;
+            }
+
+            let position = range.start + offset;
+            (position, &cells[D::index(position, size)])
+        })
+    }
+}
+
+pub struct EnumerateRangeMut<'a, Cell: 'a, D: Dim> {
+    cells: slice::IterMut<'a, Cell>,
+    size: D::Vector,
+    /// Positions in the range, for row by row layouts.
+    positions: Option<IterRange<D>>,
+    /// Indices of the cells in the range, in increasing order, with their positions. Only used
+    /// for layouts that aren't row by row.
+    sorted: vec::IntoIter<(usize, D::Point)>,
+    /// The index of the cell `cells` gives next.
+    next_index: usize,
+}
+
+impl<'a, Cell, D: Dim> Iterator for EnumerateRangeMut<'a, Cell, D> {
+    type Item = (D::Point, &'a mut Cell);
+
+    fn next(&mut self) -> Option<(D::Point, &'a mut Cell)> {
+        let (index, position) = match self.positions {
+            Some(ref mut positions) => {
+                let position = positions.next()?;
+                (D::index(position, self.size), position)
+            },
+            None => self.sorted.next()?,
+        };
+        let cell = self.cells.nth(index - self.next_index);
+        self.next_index = index + 1;
+
+        cell.map(|cell| (position, cell))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point3, Vector3},
+
+        grid::{Grid, Voxels, Range, Tracking},
+    };
+
+    #[test]
+    fn iterators_match_positions() {
+        let mut voxels: Voxels<i32> = Grid::empty(Vector3::new(4, 3, 2));
+        for (position, cell) in voxels.enumerate_mut() {
+            *cell = position.x + position.y * 10 + position.z * 100;
+        }
+
+        assert!(voxels.enumerate().all(|(p, cell)| *cell == p.x + p.y * 10 + p.z * 100));
+        assert_eq!(voxels.iter().count(), 4 * 3 * 2);
+        assert_eq!(voxels.iter().nth(5), voxels.get(Point3::new(1, 1, 0)).ok());
+
+        let range = Range::new_dim3(2, 1, 1, 6, 6, 6);
+        let cells: Vec<_> = voxels.enumerate_range(range).map(|(_, c)| *c).collect();
+        assert_eq!(cells, vec![112, 113, 122, 123]);
+    }
+
+    #[test]
+    fn mutable_range_iteration_only_touches_range() {
+        let mut voxels: Voxels<u8> = Grid::empty(Vector3::new(5, 5, 5));
+        voxels.track_changes(Tracking::Positions);
+
+        for (_, cell) in voxels.enumerate_range_mut(Range::new_dim3(1, 1, 1, 2, 3, 4)) {
+            *cell = 1;
+        }
+
+        assert_eq!(voxels.iter().filter(|c| **c == 1).count(), 2 * 3 * 4);
+        assert_eq!(voxels.get(Point3::new(2, 3, 4)), Ok(&1));
+        assert_eq!(voxels.get(Point3::new(3, 3, 4)), Ok(&0));
+        assert_eq!(voxels.drain_changes().len(), 2 * 3 * 4);
+
+        assert_eq!(voxels.enumerate_range_mut(Range::new_dim3(5, 0, 0, 6, 6, 6)).count(), 0);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_iterators_match_serial() {
+        use rayon::prelude::*;
+
+        let mut voxels: Voxels<i32> = Grid::empty(Vector3::new(17, 9, 5));
+        voxels.par_enumerate_mut().for_each(|(p, cell)| *cell = p.x * p.y - p.z);
+
+        assert!(voxels.enumerate().all(|(p, cell)| *cell == p.x * p.y - p.z));
+        assert_eq!(voxels.par_iter().sum::<i32>(), voxels.iter().sum::<i32>());
+
+        let range = Range::new_dim3(3, 2, 1, 20, 4, 3);
+        let mut parallel: Vec<_> = voxels.par_enumerate_range(range).collect();
+        let serial: Vec<_> = voxels.enumerate_range(range).collect();
+        parallel.sort_by_key(|&(p, _)| (p.z, p.y, p.x));
+        assert_eq!(parallel, serial);
+    }
+}
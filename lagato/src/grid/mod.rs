@@ -1,12 +1,42 @@
+mod automaton;
+mod changes;
+mod chunked;
+mod compressed;
 mod dim;
+mod flood;
 mod grid;
+mod heightmap;
+mod hex;
+mod iter;
+mod morton;
+mod octree;
 mod range;
+mod region;
+mod spatial;
+mod storage;
+mod transform;
+mod visibility;
 
 pub use self::{
-    dim::{Dim, Dim2, Dim3},
+    automaton::{Automaton, Edges, Neighborhood},
+    changes::{Tracking},
+    chunked::{ChunkedGrid},
+    compressed::{FormatError},
+    dim::{Dim, Dim2, Dim3, Axis, Connectivity, Neighbors},
     grid::{Grid, Error},
+    heightmap::{HydraulicErosion},
+    hex::{DimHex, HexRing, HexSpiral, HexLine, HexOrientation, HexLayout},
+    iter::{EnumerateRangeMut},
+    morton::{Dim3Morton},
+    octree::{Octree, IterLeaves},
     range::{Range, IterRange},
+    spatial::{SpatialHash},
+    storage::{Storage, BitStorage, PaletteStorage},
+    transform::{Anchor},
+    visibility::{Line},
 };
 
 pub type Tiles<Tile> = Grid<Tile, Dim2>;
 pub type Voxels<Voxel> = Grid<Voxel, Dim3>;
+pub type HexTiles<Tile> = Grid<Tile, DimHex>;
+pub type MortonVoxels<Voxel> = Grid<Voxel, Dim3Morton>;
@@ -0,0 +1,204 @@
+use {
+    cgmath::{Vector3, Point3},
+
+    grid::{Dim, Dim3, Axis, Connectivity},
+};
+
+/// Three dimensions like `Dim3`, but with cells stored in Morton order (also called Z-order)
+/// instead of row by row. Cells that are close together in any direction are mostly close
+/// together in memory too, which makes walking along Z and sampling neighborhoods much more
+/// cache friendly.
+///
+/// Grids using this layout need a power of two size along every axis. The cells are split into
+/// cubes as large as the smallest axis, each stored in Morton order, with the cubes stored one
+/// after another.
+/// Iterating positions still goes row by row, only storage order differs.
+pub enum Dim3Morton {}
+
+impl Dim for Dim3Morton {
+    type Vector = Vector3<i32>;
+    type Point = Point3<i32>;
+    type FloatPoint = Point3<f32>;
+
+    fn start() -> Point3<i32> {
+        Dim3::start()
+    }
+
+    fn end(size: Vector3<i32>) -> Point3<i32> {
+        Dim3::end(size)
+    }
+
+    fn floor(position: Point3<f32>) -> Point3<i32> {
+        Dim3::floor(position)
+    }
+
+    fn ceil(position: Point3<f32>) -> Point3<i32> {
+        Dim3::ceil(position)
+    }
+
+    fn splat(value: i32) -> Vector3<i32> {
+        Dim3::splat(value)
+    }
+
+    fn axes() -> &'static [Axis] {
+        Dim3::axes()
+    }
+
+    fn component(vector: Vector3<i32>, axis: Axis) -> i32 {
+        Dim3::component(vector, axis)
+    }
+
+    fn with_component(vector: Vector3<i32>, axis: Axis, value: i32) -> Vector3<i32> {
+        Dim3::with_component(vector, axis, value)
+    }
+
+    fn min(a: Point3<i32>, b: Point3<i32>) -> Point3<i32> {
+        Dim3::min(a, b)
+    }
+
+    fn max(a: Point3<i32>, b: Point3<i32>) -> Point3<i32> {
+        Dim3::max(a, b)
+    }
+
+    fn area(size: Vector3<i32>) -> usize {
+        Dim3::area(size)
+    }
+
+    fn is_valid_size(size: Vector3<i32>) -> bool {
+        let is_power_of_two = |v: i32| v > 0 && (v & (v - 1)) == 0;
+        is_power_of_two(size.x) && is_power_of_two(size.y) && is_power_of_two(size.z)
+    }
+
+    fn is_row_major() -> bool {
+        false
+    }
+
+    fn is_in_bounds(position: Point3<i32>, size: Vector3<i32>) -> bool {
+        Dim3::is_in_bounds(position, size)
+    }
+
+    fn index(position: Point3<i32>, size: Vector3<i32>) -> usize {
+        let (bits, blocks) = block_layout(size);
+        let mask = (1 << bits) - 1;
+
+        let within = spread(position.x & mask)
+            | (spread(position.y & mask) << 1)
+            | (spread(position.z & mask) << 2);
+        let block = Dim3::index(
+            Point3::new(position.x >> bits, position.y >> bits, position.z >> bits), blocks,
+        );
+
+        within as usize | (block << (bits * 3))
+    }
+
+    fn position(index: usize, size: Vector3<i32>) -> Point3<i32> {
+        let (bits, blocks) = block_layout(size);
+        let within = index as u64 & ((1 << (bits * 3)) - 1);
+        let block = Dim3::position(index >> (bits * 3), blocks);
+
+        Point3::new(
+            (block.x << bits) | compact(within),
+            (block.y << bits) | compact(within >> 1),
+            (block.z << bits) | compact(within >> 2),
+        )
+    }
+
+    fn next(position: Point3<i32>, start: Point3<i32>, end: Point3<i32>) -> Option<Point3<i32>> {
+        Dim3::next(position, start, end)
+    }
+
+    fn to_chunk(position: Point3<i32>, chunk_size: Vector3<i32>) -> (Point3<i32>, Point3<i32>) {
+        Dim3::to_chunk(position, chunk_size)
+    }
+
+    fn from_chunk(
+        chunk: Point3<i32>, local: Point3<i32>, chunk_size: Vector3<i32>
+    ) -> Point3<i32> {
+        Dim3::from_chunk(chunk, local, chunk_size)
+    }
+
+    fn neighbor_offsets(connectivity: Connectivity) -> Vec<Vector3<i32>> {
+        Dim3::neighbor_offsets(connectivity)
+    }
+
+    fn distance(a: Point3<i32>, b: Point3<i32>, connectivity: Connectivity) -> i32 {
+        Dim3::distance(a, b, connectivity)
+    }
+}
+
+/// The amount of bits per axis in a Morton ordered cube, and the amount of cubes along every
+/// axis.
+fn block_layout(size: Vector3<i32>) -> (i32, Vector3<i32>) {
+    let bits = size.x.min(size.y).min(size.z).max(1).trailing_zeros() as i32;
+    (bits, Vector3::new(size.x >> bits, size.y >> bits, size.z >> bits))
+}
+
+/// Spreads out the lower 21 bits of a value so there's two zero bits between every bit.
+fn spread(value: i32) -> u64 {
+    let mut value = value as u64 & 0x1f_ffff;
+    value = (value | value << 32) & 0x001f_0000_0000_ffff;
+    value = (value | value << 16) & 0x001f_0000_ff00_00ff;
+    value = (value | value << 8) & 0x100f_00f0_0f00_f00f;
+    value = (value | value << 4) & 0x10c3_0c30_c30c_30c3;
+    value = (value | value << 2) & 0x1249_2492_4924_9249;
+    value
+}
+
+/// The inverse of `spread`, collecting every third bit.
+fn compact(value: u64) -> i32 {
+    let mut value = value & 0x1249_2492_4924_9249;
+    value = (value ^ (value >> 2)) & 0x10c3_0c30_c30c_30c3;
+    value = (value ^ (value >> 4)) & 0x100f_00f0_0f00_f00f;
+    value = (value ^ (value >> 8)) & 0x001f_0000_ff00_00ff;
+    value = (value ^ (value >> 16)) & 0x001f_0000_0000_ffff;
+    value = (value ^ (value >> 32)) & 0x1f_ffff;
+    value as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        std::collections::{HashSet},
+        cgmath::{Point3, Vector3},
+
+        grid::{Dim, Dim3Morton, Grid, Range, MortonVoxels},
+    };
+
+    #[test]
+    fn index_is_a_compact_bijection() {
+        for size in &[Vector3::new(8, 8, 8), Vector3::new(4, 16, 2), Vector3::new(1, 1, 32)] {
+            let mut indices = HashSet::new();
+            for position in Range::<Dim3Morton>::new(Dim3Morton::start(), Dim3Morton::end(*size))
+                .iter()
+            {
+                let index = Dim3Morton::index(position, *size);
+                assert!(index < Dim3Morton::area(*size));
+                assert_eq!(Dim3Morton::position(index, *size), position);
+                indices.insert(index);
+            }
+            assert_eq!(indices.len(), Dim3Morton::area(*size));
+        }
+
+        // Neighbors along z stay close in memory
+        let size = Vector3::new(32, 32, 32);
+        assert_eq!(Dim3Morton::index(Point3::new(0, 0, 1), size), 4);
+    }
+
+    #[test]
+    fn grids_work_with_morton_layout() {
+        let mut voxels: MortonVoxels<i32> = Grid::empty(Vector3::new(4, 8, 2));
+        for (position, cell) in voxels.enumerate_mut() {
+            *cell = position.x + position.y * 10 + position.z * 100;
+        }
+
+        assert_eq!(voxels.get(Point3::new(3, 7, 1)), Ok(&173));
+        assert!(voxels.enumerate().all(|(p, cell)| *cell == p.x + p.y * 10 + p.z * 100));
+
+        let range = Range::new(Point3::new(1, 2, 0), Point3::new(2, 3, 1));
+        for (position, cell) in voxels.enumerate_range_mut(range) {
+            assert!(range.contains(position));
+            *cell = -1;
+        }
+        assert_eq!(voxels.iter().filter(|c| **c == -1).count(), 8);
+    }
+}
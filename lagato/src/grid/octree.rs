@@ -0,0 +1,420 @@
+use {
+    std::mem,
+    cgmath::{Point3, Vector3, InnerSpace},
+
+    camera::{Ray},
+    grid::{Dim, Dim3, Grid, Voxels, Range, Error},
+};
+
+/// A sparse cube of voxels, starting at the origin and `2^depth` voxels large along every axis.
+/// Areas where all voxels are the same, including empty areas, are stored as a single node.
+pub struct Octree<T> {
+    root: Node<T>,
+    depth: u32,
+}
+
+#[derive(Clone, PartialEq)]
+enum Node<T> {
+    Leaf(Option<T>),
+    /// Always has 8 children, ordered by x, then y, then z.
+    Branch(Vec<Node<T>>),
+}
+
+impl<T: Clone + PartialEq> Octree<T> {
+    /// Creates an empty octree. The depth can be at most 30.
+    pub fn new(depth: u32) -> Self {
+        assert!(depth <= 30, "Octree depth too large");
+
+        Octree {
+            root: Node::Leaf(None),
+            depth,
+        }
+    }
+
+    /// Creates the smallest octree containing every cell of the grid. Cells equal to the
+    /// default value are left empty.
+    pub fn from_voxels(voxels: &Voxels<T>) -> Self where T: Default {
+        let size = voxels.size();
+        let largest = size.x.max(size.y).max(size.z).max(1);
+        let depth = 32 - (largest as u32 - 1).leading_zeros();
+
+        let empty = T::default();
+        let root = build_node(voxels, &empty, Point3::new(0, 0, 0), 1 << depth);
+        Octree {
+            root,
+            depth,
+        }
+    }
+
+    /// Converts the octree into a dense grid covering the whole octree. Empty voxels are set to
+    /// the default value.
+    pub fn to_voxels(&self) -> Voxels<T> where T: Default {
+        let mut voxels = Grid::empty(Vector3::new(self.size(), self.size(), self.size()));
+        for (range, value) in self.iter_range(self.range()) {
+            voxels.fill(range, value.clone());
+        }
+        voxels
+    }
+
+    /// Sets a voxel, returning the previous value.
+    pub fn insert(&mut self, position: Point3<i32>, value: T) -> Result<Option<T>, Error> {
+        self.replace(position, Some(value))
+    }
+
+    /// Empties a voxel, returning the previous value.
+    pub fn remove(&mut self, position: Point3<i32>) -> Result<Option<T>, Error> {
+        self.replace(position, None)
+    }
+
+    fn replace(&mut self, position: Point3<i32>, value: Option<T>) -> Result<Option<T>, Error> {
+        if !self.is_in_bounds(position) {
+            return Err(Error::OutOfBounds)
+        }
+
+        let size = self.size();
+        Ok(replace_in_node(&mut self.root, Point3::new(0, 0, 0), size, position, value))
+    }
+}
+
+impl<T> Octree<T> {
+    pub fn depth(&self) -> u32 {
+        self.depth
+    }
+
+    /// The amount of voxels along every axis.
+    pub fn size(&self) -> i32 {
+        1 << self.depth
+    }
+
+    pub fn range(&self) -> Range<Dim3> {
+        Range::new(Point3::new(0, 0, 0), Dim3::end(Dim3::splat(self.size())))
+    }
+
+    pub fn is_in_bounds(&self, position: Point3<i32>) -> bool {
+        Dim3::is_in_bounds(position, Dim3::splat(self.size()))
+    }
+
+    pub fn get(&self, position: Point3<i32>) -> Option<&T> {
+        if !self.is_in_bounds(position) {
+            return None
+        }
+
+        match *self.leaf_at(position).2 {
+            Node::Leaf(ref value) => value.as_ref(),
+            Node::Branch(_) => unreachable!(),
+        }
+    }
+
+    /// Iterates over all non-empty areas within the range. Every area is a part of a leaf node,
+    /// clipped to the range, with all voxels in it having the same value.
+    pub fn iter_range(&self, range: Range<Dim3>) -> IterLeaves<'_, T> {
+        IterLeaves {
+            range,
+            stack: vec![(&self.root, Point3::new(0, 0, 0), self.size())],
+        }
+    }
+
+    /// Finds the first non-empty voxel hit by a ray within `radius` distance, returning its
+    /// position, the normal of the face that was hit and its value. If the ray starts inside a
+    /// non-empty voxel, the normal is zero.
+    pub fn cast_ray(
+        &self, ray: &Ray, radius: f32,
+    ) -> Option<(Point3<i32>, Vector3<i32>, &T)> {
+        let origin = ray.origin;
+        let direction = ray.direction;
+        let size = self.size();
+        assert!(direction.magnitude2() > 0.0, "Raycast in zero direction");
+
+        // Find where the ray enters the octree's bounds, if it does at all
+        let mut t_enter = 0.0f32;
+        let mut t_exit = radius / direction.magnitude();
+        let mut normal = Vector3::new(0, 0, 0);
+        for axis in 0..3 {
+            if direction[axis] == 0.0 {
+                if origin[axis] < 0.0 || origin[axis] >= size as f32 { return None }
+                continue
+            }
+
+            let mut t_near = -origin[axis] / direction[axis];
+            let mut t_far = (size as f32 - origin[axis]) / direction[axis];
+            if t_near > t_far { mem::swap(&mut t_near, &mut t_far) }
+
+            if t_near > t_enter {
+                t_enter = t_near;
+                normal = Vector3::new(0, 0, 0);
+                normal[axis] = -signum(direction[axis]);
+            }
+            t_exit = t_exit.min(t_far);
+        }
+        if t_enter > t_exit {
+            return None
+        }
+
+        let entry = origin + direction * t_enter;
+        let mut voxel = entry.map(|v| (v.floor() as i32).max(0).min(size - 1));
+        let mut t = t_enter;
+
+        loop {
+            let (leaf_origin, leaf_size, node) = self.leaf_at(voxel);
+            if let Node::Leaf(Some(ref value)) = *node {
+                return Some((voxel, normal, value))
+            }
+
+            // Skip past the entire empty leaf, to the face the ray leaves it through
+            let mut exit_axis = 0;
+            let mut t_leaf_exit = f32::INFINITY;
+            for axis in 0..3 {
+                if direction[axis] == 0.0 { continue }
+
+                let boundary = if direction[axis] > 0.0 {
+                    leaf_origin[axis] + leaf_size
+                } else {
+                    leaf_origin[axis]
+                };
+                let t_axis = (boundary as f32 - origin[axis]) / direction[axis];
+                if t_axis < t_leaf_exit {
+                    t_leaf_exit = t_axis;
+                    exit_axis = axis;
+                }
+            }
+
+            t = t.max(t_leaf_exit);
+            if t > t_exit {
+                return None
+            }
+
+            // Other axes stay within the leaf, so rounding errors can't skip over voxels
+            let point = origin + direction * t;
+            let step = signum(direction[exit_axis]);
+            for axis in 0..3 {
+                voxel[axis] = if axis == exit_axis {
+                    if step > 0 { leaf_origin[axis] + leaf_size } else { leaf_origin[axis] - 1 }
+                } else {
+                    (point[axis].floor() as i32)
+                        .max(leaf_origin[axis])
+                        .min(leaf_origin[axis] + leaf_size - 1)
+                };
+            }
+            normal = Vector3::new(0, 0, 0);
+            normal[exit_axis] = -step;
+
+            if !self.is_in_bounds(voxel) {
+                return None
+            }
+        }
+    }
+
+    /// Finds the leaf node containing a position, with its origin and size.
+    fn leaf_at(&self, position: Point3<i32>) -> (Point3<i32>, i32, &Node<T>) {
+        let mut node = &self.root;
+        let mut origin = Point3::new(0, 0, 0);
+        let mut size = self.size();
+
+        while let Node::Branch(ref children) = *node {
+            size /= 2;
+            let index = child_index(origin, size, position);
+            origin += child_offset(index, size);
+            node = &children[index];
+        }
+
+        (origin, size, node)
+    }
+}
+
+pub struct IterLeaves<'a, T: 'a> {
+    range: Range<Dim3>,
+    stack: Vec<(&'a Node<T>, Point3<i32>, i32)>,
+}
+
+impl<'a, T> Iterator for IterLeaves<'a, T> {
+    type Item = (Range<Dim3>, &'a T);
+
+    fn next(&mut self) -> Option<(Range<Dim3>, &'a T)> {
+        while let Some((node, origin, size)) = self.stack.pop() {
+            let node_range = Range::new(origin, origin + Dim3::splat(size - 1));
+            let range = match node_range.intersection(&self.range) {
+                Some(range) => range,
+                None => continue,
+            };
+
+            match *node {
+                Node::Leaf(Some(ref value)) => return Some((range, value)),
+                Node::Leaf(None) => {},
+                Node::Branch(ref children) => {
+                    // Pushed in reverse so children come out in order
+                    let half = size / 2;
+                    for (index, child) in children.iter().enumerate().rev() {
+                        self.stack.push((child, origin + child_offset(index, half), half));
+                    }
+                },
+            }
+        }
+
+        None
+    }
+}
+
+fn build_node<T: Clone + PartialEq>(
+    voxels: &Voxels<T>, empty: &T, origin: Point3<i32>, size: i32,
+) -> Node<T> {
+    if size == 1 {
+        return match voxels.get(origin) {
+            Ok(value) if value != empty => Node::Leaf(Some(value.clone())),
+            _ => Node::Leaf(None),
+        }
+    }
+
+    let half = size / 2;
+    let children = (0..8)
+        .map(|index| build_node(voxels, empty, origin + child_offset(index, half), half))
+        .collect();
+
+    let mut node = Node::Branch(children);
+    collapse(&mut node);
+    node
+}
+
+fn replace_in_node<T: Clone + PartialEq>(
+    node: &mut Node<T>, origin: Point3<i32>, size: i32, position: Point3<i32>, value: Option<T>,
+) -> Option<T> {
+    if let Node::Leaf(ref mut current) = *node {
+        if size == 1 {
+            return mem::replace(current, value)
+        }
+        if *current == value {
+            return value
+        }
+    }
+
+    // Split uniform leaves so only the one voxel changes
+    let split = match *node {
+        Node::Leaf(ref current) => Some(current.clone()),
+        Node::Branch(_) => None,
+    };
+    if let Some(current) = split {
+        *node = Node::Branch(vec![Node::Leaf(current); 8]);
+    }
+
+    let previous = match *node {
+        Node::Branch(ref mut children) => {
+            let half = size / 2;
+            let index = child_index(origin, half, position);
+            let child_origin = origin + child_offset(index, half);
+            replace_in_node(&mut children[index], child_origin, half, position, value)
+        },
+        Node::Leaf(_) => unreachable!(),
+    };
+
+    collapse(node);
+    previous
+}
+
+/// Turns a branch into a single leaf if all of its children are the same leaf.
+fn collapse<T: PartialEq>(node: &mut Node<T>) {
+    let uniform = match *node {
+        Node::Branch(ref mut children) => {
+            let is_uniform = children.iter().all(|child| match *child {
+                Node::Leaf(_) => *child == children[0],
+                Node::Branch(_) => false,
+            });
+            if is_uniform { Some(children.swap_remove(0)) } else { None }
+        },
+        Node::Leaf(_) => None,
+    };
+
+    if let Some(leaf) = uniform {
+        *node = leaf;
+    }
+}
+
+fn child_index(origin: Point3<i32>, half: i32, position: Point3<i32>) -> usize {
+    let mut index = 0;
+    if position.x >= origin.x + half { index |= 1 }
+    if position.y >= origin.y + half { index |= 2 }
+    if position.z >= origin.z + half { index |= 4 }
+    index
+}
+
+fn child_offset(index: usize, half: i32) -> Vector3<i32> {
+    Vector3::new(
+        (index & 1) as i32 * half,
+        ((index >> 1) & 1) as i32 * half,
+        ((index >> 2) & 1) as i32 * half,
+    )
+}
+
+fn signum(value: f32) -> i32 {
+    if value > 0.0 { 1 } else if value < 0.0 { -1 } else { 0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point3, Vector3},
+
+        camera::{Ray},
+        grid::{Grid, Voxels, Octree, Range},
+        grid::octree::{Node},
+    };
+
+    #[test]
+    fn insert_and_remove_collapse_nodes() {
+        let mut octree = Octree::new(3);
+        assert_eq!(octree.insert(Point3::new(1, 2, 3), 5u8), Ok(None));
+        assert_eq!(octree.insert(Point3::new(1, 2, 3), 6), Ok(Some(5)));
+        assert_eq!(octree.get(Point3::new(1, 2, 3)), Some(&6));
+        assert_eq!(octree.get(Point3::new(1, 2, 4)), None);
+        assert!(octree.insert(Point3::new(8, 0, 0), 1).is_err());
+
+        assert_eq!(octree.remove(Point3::new(1, 2, 3)), Ok(Some(6)));
+        assert!(octree.root == Node::Leaf(None));
+
+        for position in Range::new_dim3(0, 0, 0, 1, 1, 1).iter() {
+            octree.insert(position, 1).unwrap();
+        }
+        assert_eq!(octree.iter_range(octree.range()).count(), 1);
+        assert_eq!(octree.iter_range(Range::new_dim3(1, 1, 1, 4, 4, 4)).next().unwrap().0,
+            Range::new_dim3(1, 1, 1, 1, 1, 1));
+    }
+
+    #[test]
+    fn converts_to_and_from_voxels() {
+        let mut voxels: Voxels<u16> = Grid::empty(Vector3::new(5, 9, 3));
+        voxels.fill(Range::new_dim3(0, 0, 0, 4, 3, 2), 2);
+        *voxels.get_mut(Point3::new(4, 8, 2)).unwrap() = 7;
+
+        let octree = Octree::from_voxels(&voxels);
+        assert_eq!(octree.size(), 16);
+        assert_eq!(octree.get(Point3::new(4, 8, 2)), Some(&7));
+        assert_eq!(octree.get(Point3::new(4, 8, 1)), None);
+
+        let converted = octree.to_voxels();
+        for position in voxels.iter_pos() {
+            assert_eq!(converted.get(position), voxels.get(position));
+        }
+    }
+
+    #[test]
+    fn rays_hit_first_voxel() {
+        let mut octree = Octree::new(5);
+        octree.insert(Point3::new(20, 3, 3), true).unwrap();
+        octree.insert(Point3::new(25, 3, 3), true).unwrap();
+
+        let ray = Ray {
+            origin: Point3::new(-4.0, 3.5, 3.5),
+            direction: Vector3::new(1.0, 0.0, 0.0),
+        };
+        let (voxel, normal, _) = octree.cast_ray(&ray, 100.0).unwrap();
+        assert_eq!(voxel, Point3::new(20, 3, 3));
+        assert_eq!(normal, Vector3::new(-1, 0, 0));
+        assert!(octree.cast_ray(&ray, 20.0).is_none());
+
+        let diagonal = Ray {
+            origin: Point3::new(10.5, 13.8, 3.5),
+            direction: Vector3::new(1.0, -1.0, 0.0),
+        };
+        let (voxel, normal, _) = octree.cast_ray(&diagonal, 100.0).unwrap();
+        assert_eq!(voxel, Point3::new(20, 3, 3));
+        assert_eq!(normal, Vector3::new(0, 1, 0));
+    }
+}
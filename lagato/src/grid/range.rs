@@ -1,29 +1,177 @@
 use {
+    std::fmt::{self, Debug, Formatter},
     cgmath::{Point2, Point3},
-    grid::{Dim, Dim2, Dim3},
+    grid::{Dim, Dim2, Dim3, Axis},
 };
 
-#[derive(Clone, Copy)]
 pub struct Range<D: Dim> {
     pub start: D::Point,
     /// Inclusive
     pub end: D::Point,
 }
 
+// Deriving these would require D itself to be Clone and Copy, which dimensions aren't
+impl<D: Dim> Clone for Range<D> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<D: Dim> Copy for Range<D> {}
+
+impl<D: Dim> PartialEq for Range<D> {
+    fn eq(&self, other: &Self) -> bool {
+        self.start == other.start && self.end == other.end
+    }
+}
+
+impl<D: Dim> Eq for Range<D> {}
+
+impl<D: Dim> Debug for Range<D> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("Range").field("start", &self.start).field("end", &self.end).finish()
+    }
+}
+
 impl<D: Dim> Range<D> {
     pub fn new(start: D::Point, end: D::Point) -> Self {
         Range { start, end }
     }
 
+    /// The amount of positions in this range along every axis.
+    pub fn size(&self) -> D::Vector {
+        (self.end - self.start) + D::splat(1)
+    }
+
+    /// Returns true if the start is past the end on any axis, so the range has no positions.
+    pub fn is_empty(&self) -> bool {
+        D::min(self.start, self.end) != self.start
+    }
+
+    /// The amount of positions in this range.
+    pub fn volume(&self) -> usize {
+        if self.is_empty() { 0 } else { D::area(self.size()) }
+    }
+
+    pub fn contains(&self, position: D::Point) -> bool {
+        D::min(self.start, position) == self.start && D::max(self.end, position) == self.end
+    }
+
+    /// Checks if all positions of another range are in this range.
+    pub fn contains_range(&self, other: &Range<D>) -> bool {
+        other.is_empty() || (self.contains(other.start) && self.contains(other.end))
+    }
+
+    /// Returns the positions that are in both ranges, or `None` if they don't overlap.
+    pub fn intersection(&self, other: &Range<D>) -> Option<Range<D>> {
+        let range = Range::new(D::max(self.start, other.start), D::min(self.end, other.end));
+        if range.is_empty() { None } else { Some(range) }
+    }
+
+    /// Returns the smallest range containing both ranges.
+    pub fn union(&self, other: &Range<D>) -> Range<D> {
+        if self.is_empty() { return *other }
+        if other.is_empty() { return *self }
+
+        Range::new(D::min(self.start, other.start), D::max(self.end, other.end))
+    }
+
+    pub fn translate(&self, offset: D::Vector) -> Range<D> {
+        Range::new(self.start + offset, self.end + offset)
+    }
+
+    /// Grows the range by `margin` on every side, or shrinks it if the margin is negative.
+    pub fn expand(&self, margin: i32) -> Range<D> {
+        let margin = D::splat(margin);
+        Range::new(self.start - margin, self.end + margin)
+    }
+
+    /// Returns the part of this range that's within a grid of the given size, or `None` if none
+    /// of it is.
+    pub fn clamp_to_size(&self, size: D::Vector) -> Option<Range<D>> {
+        self.intersection(&Range::new(D::start(), D::end(size)))
+    }
+
+    /// Splits this range up along chunk boundaries, returning every chunk the range overlaps
+    /// together with the part of the range within that chunk.
+    pub fn split_chunks(&self, chunk_size: D::Vector) -> Vec<(D::Point, Range<D>)> {
+        if self.is_empty() {
+            return Vec::new()
+        }
+
+        let (start_chunk, _) = D::to_chunk(self.start, chunk_size);
+        let (end_chunk, _) = D::to_chunk(self.end, chunk_size);
+
+        Range::<D>::new(start_chunk, end_chunk).iter()
+            .map(|chunk| {
+                let chunk_range = Range::new(
+                    D::from_chunk(chunk, D::start(), chunk_size),
+                    D::from_chunk(chunk, D::end(chunk_size), chunk_size),
+                );
+                (chunk, self.intersection(&chunk_range).unwrap())
+            })
+            .collect()
+    }
+
+    /// Merges ranges that line up into larger ranges, covering exactly the same positions with
+    /// fewer ranges. Useful for turning many small changes into a few larger ones.
+    pub fn coalesce(ranges: &[Range<D>]) -> Vec<Range<D>> {
+        let mut ranges: Vec<Range<D>> = ranges.iter().cloned().filter(|r| !r.is_empty()).collect();
+
+        for axis in D::axes() {
+            // Ranges can only be merged along this axis if they're the same on all other axes,
+            // sorting brings those next to each other
+            let mut keyed: Vec<(Vec<i32>, Range<D>)> = ranges.drain(..)
+                .map(|range| {
+                    let mut key = Vec::new();
+                    for other in D::axes().iter().filter(|a| *a != axis) {
+                        key.push(component::<D>(range.start, *other));
+                        key.push(component::<D>(range.end, *other));
+                    }
+                    (key, range)
+                })
+                .collect();
+            keyed.sort_by_key(|&(ref key, range)| {
+                (key.clone(), component::<D>(range.start, *axis))
+            });
+
+            let mut previous_key = None;
+            for (key, range) in keyed {
+                if previous_key.as_ref() == Some(&key) {
+                    let last = ranges.last_mut().unwrap();
+                    let last_end = component::<D>(last.end, *axis);
+                    if component::<D>(range.start, *axis) <= last_end + 1 {
+                        let end = last_end.max(component::<D>(range.end, *axis));
+                        last.end = with_component::<D>(last.end, *axis, end);
+                        continue
+                    }
+                }
+
+                ranges.push(range);
+                previous_key = Some(key);
+            }
+        }
+
+        ranges
+    }
+
     pub fn iter(self) -> IterRange<D> {
-        let start = self.start;
+        let start = if self.is_empty() { None } else { Some(self.start) };
         IterRange {
             range: self,
-            next_position: Some(start),
+            next_position: start,
         }
     }
 }
 
+fn component<D: Dim>(point: D::Point, axis: Axis) -> i32 {
+    D::component(point - D::start(), axis)
+}
+
+fn with_component<D: Dim>(point: D::Point, axis: Axis, value: i32) -> D::Point {
+    D::start() + D::with_component(point - D::start(), axis, value)
+}
+
 impl Range<Dim2> {
     pub fn new_dim2(
         start_x: i32, start_y: i32, end_x: i32, end_y: i32
@@ -58,3 +206,45 @@ impl<D: Dim> Iterator for IterRange<D> {
         position
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Point3, Vector2, Vector3},
+
+        grid::{Range},
+    };
+
+    #[test]
+    fn set_operations_work() {
+        let a = Range::new_dim2(0, 0, 4, 4);
+        let b = Range::new_dim2(3, -2, 6, 2);
+
+        assert_eq!(a.intersection(&b), Some(Range::new_dim2(3, 0, 4, 2)));
+        assert_eq!(a.union(&b), Range::new_dim2(0, -2, 6, 4));
+        assert_eq!(a.intersection(&a.translate(Vector2::new(5, 0))), None);
+
+        assert!(a.contains(Point2::new(4, 0)));
+        assert!(!a.contains(Point2::new(5, 0)));
+        assert!(a.contains_range(&Range::new_dim2(1, 1, 3, 3)));
+        assert!(!a.contains_range(&b));
+
+        assert_eq!(a.volume(), 25);
+        assert_eq!(a.expand(1), Range::new_dim2(-1, -1, 5, 5));
+        assert_eq!(a.expand(-3).volume(), 0);
+        assert_eq!(b.clamp_to_size(Vector2::new(5, 5)), Some(Range::new_dim2(3, 0, 4, 2)));
+    }
+
+    #[test]
+    fn split_chunks_covers_range() {
+        let range = Range::new_dim3(-2, 0, 0, 9, 3, 3);
+        let chunks = range.split_chunks(Vector3::new(8, 8, 8));
+
+        assert_eq!(chunks, vec![
+            (Point3::new(-1, 0, 0), Range::new_dim3(-2, 0, 0, -1, 3, 3)),
+            (Point3::new(0, 0, 0), Range::new_dim3(0, 0, 0, 7, 3, 3)),
+            (Point3::new(1, 0, 0), Range::new_dim3(8, 0, 0, 9, 3, 3)),
+        ]);
+        assert_eq!(chunks.iter().map(|&(_, r)| r.volume()).sum::<usize>(), range.volume());
+    }
+}
@@ -0,0 +1,145 @@
+use {
+    grid::{Dim, Grid, Range, Storage, Error},
+};
+
+impl<Cell: Clone, D: Dim, S: Storage<Cell>> Grid<Cell, D, S> {
+    /// Sets every cell in the range to a value. Parts of the range outside of the grid are
+    /// ignored.
+    pub fn fill(&mut self, range: Range<D>, value: Cell) {
+        let range = match range.intersection(&self.range()) {
+            Some(range) => range,
+            None => return,
+        };
+
+        for position in range.iter() {
+            self.set(position, value.clone()).unwrap();
+        }
+    }
+
+    /// Sets every cell in the range to the value returned for its position. Parts of the range
+    /// outside of the grid are ignored.
+    pub fn fill_with<F: FnMut(D::Point) -> Cell>(&mut self, range: Range<D>, mut value: F) {
+        let range = match range.intersection(&self.range()) {
+            Some(range) => range,
+            None => return,
+        };
+
+        for position in range.iter() {
+            self.set(position, value(position)).unwrap();
+        }
+    }
+
+    /// Copies the cells in the range into a new grid. The range has to be entirely within this
+    /// grid, and its size has to be valid for the dimension or `Error::SizeMismatch` is returned.
+    pub fn copy_region(&self, range: Range<D>) -> Result<Grid<Cell, D, S>, Error> {
+        if range.is_empty() || !self.is_in_bounds(range.start) || !self.is_in_bounds(range.end) {
+            return Err(Error::OutOfBounds)
+        }
+        if !D::is_valid_size(range.size()) {
+            return Err(Error::SizeMismatch)
+        }
+
+        let mut region = Grid::filled(range.size(), self.value(range.start)?);
+        for position in range.iter() {
+            region.set(position - (range.start - D::start()), self.value(position)?)?;
+        }
+
+        Ok(region)
+    }
+
+    /// Copies all cells from another grid into this grid, with the other grid's origin placed at
+    /// `offset`. Cells that would end up outside of this grid are skipped.
+    pub fn paste<S2: Storage<Cell>>(&mut self, source: &Grid<Cell, D, S2>, offset: D::Vector) {
+        self.blit(source, offset, |_, source| source);
+    }
+
+    /// Combines all cells from another grid with the cells of this grid, with the other grid's
+    /// origin placed at `offset`. `merge` receives the current cell and the other grid's cell,
+    /// and returns the new cell. Cells that would end up outside of this grid are skipped.
+    pub fn blit<Source, S2, F>(
+        &mut self, source: &Grid<Source, D, S2>, offset: D::Vector, mut merge: F
+    ) where
+        S2: Storage<Source>,
+        F: FnMut(Cell, Source) -> Cell,
+    {
+        let source_range = source.range();
+        let target_range = Range::new(source_range.start + offset, source_range.end + offset);
+        let range = match target_range.intersection(&self.range()) {
+            Some(range) => range,
+            None => return,
+        };
+
+        for position in range.iter() {
+            let source_value = source.value(position - offset).unwrap();
+            let value = merge(self.value(position).unwrap(), source_value);
+            self.set(position, value).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Point3, Vector2, Vector3},
+
+        grid::{Grid, Tiles, Voxels, MortonVoxels, Range, Error},
+    };
+
+    #[test]
+    fn copy_and_paste_round_trips_region() {
+        let mut grid: Voxels<u8> = Grid::empty(Vector3::new(8, 8, 8));
+        grid.fill(Range::new_dim3(2, 2, 2, 4, 5, 6), 3);
+
+        let region = grid.copy_region(Range::new_dim3(1, 1, 1, 4, 4, 4)).unwrap();
+        assert_eq!(region.size(), Vector3::new(4, 4, 4));
+        assert_eq!(region.iter_pos().filter(|p| *region.get(*p).unwrap() == 3).count(), 3 * 3 * 3);
+
+        let mut target: Voxels<u8> = Grid::empty(Vector3::new(8, 8, 8));
+        target.paste(&region, Vector3::new(1, 1, 1));
+        for position in target.range().iter() {
+            let expected = if position.x >= 2 && position.y >= 2 && position.z >= 2
+                && position.x <= 4 && position.y <= 4 && position.z <= 4 { 3 } else { 0 };
+            assert_eq!(*target.get(position).unwrap(), expected);
+        }
+    }
+
+    #[test]
+    fn paste_clips_at_bounds() {
+        let mut grid: Tiles<u8> = Grid::empty(Vector2::new(4, 4));
+        let mut stamp: Tiles<u8> = Grid::empty(Vector2::new(3, 3));
+        stamp.fill(stamp.range(), 1);
+
+        grid.paste(&stamp, Vector2::new(-1, 2));
+
+        let filled: Vec<_> = grid.iter_pos().filter(|p| *grid.get(*p).unwrap() == 1).collect();
+        assert_eq!(filled, vec![
+            Point2::new(0, 2), Point2::new(1, 2), Point2::new(0, 3), Point2::new(1, 3),
+        ]);
+    }
+
+    #[test]
+    fn blit_merges_cells_and_copy_rejects_out_of_bounds() {
+        let mut grid: Tiles<u8> = Grid::empty(Vector2::new(4, 4));
+        grid.fill(Range::new_dim2(-5, -5, 1, 1), 2);
+
+        let mut stamp: Tiles<u8> = Grid::empty(Vector2::new(2, 2));
+        stamp.fill(stamp.range(), 3);
+        grid.blit(&stamp, Vector2::new(1, 1), |a, b| a + b);
+
+        assert_eq!(*grid.get(Point2::new(0, 0)).unwrap(), 2);
+        assert_eq!(*grid.get(Point2::new(1, 1)).unwrap(), 5);
+        assert_eq!(*grid.get(Point2::new(2, 2)).unwrap(), 3);
+        assert!(grid.copy_region(Range::new_dim2(2, 2, 4, 4)).is_err());
+    }
+
+    #[test]
+    fn copy_region_rejects_invalid_morton_sizes() {
+        let grid: MortonVoxels<u8> = Grid::empty(Vector3::new(8, 8, 8));
+
+        let region = grid.copy_region(Range::new(Point3::new(0, 0, 0), Point3::new(3, 1, 3)));
+        assert_eq!(region.unwrap().size(), Vector3::new(4, 2, 4));
+
+        let region = grid.copy_region(Range::new(Point3::new(0, 0, 0), Point3::new(2, 2, 2)));
+        assert_eq!(region.err(), Some(Error::SizeMismatch));
+    }
+}
@@ -0,0 +1,231 @@
+use {
+    std::{
+        collections::{HashMap, HashSet},
+        hash::{Hash},
+    },
+
+    grid::{Dim, Range},
+};
+
+/// Finds moving entities near positions, by sorting them into buckets of a fixed size.
+/// Entities are identified by an id and take up an axis-aligned box, given as a `Range` of
+/// integer positions. These positions can be finer than any grid the entities move over, for
+/// example using pixels rather than tiles. Unlike `Grid`, positions can be negative.
+pub struct SpatialHash<Id, D: Dim> {
+    bucket_size: D::Vector,
+    buckets: HashMap<D::Point, Vec<Id>>,
+    entities: HashMap<Id, Range<D>>,
+}
+
+impl<Id: Copy + Eq + Hash, D: Dim> SpatialHash<Id, D> {
+    /// Creates an empty spatial hash. Buckets should be about the size of the typical entity,
+    /// so most entities only end up in a few buckets.
+    pub fn new(bucket_size: D::Vector) -> Self {
+        assert!(
+            D::axes().iter().all(|axis| D::component(bucket_size, *axis) > 0),
+            "Bucket size must be larger than zero along every axis",
+        );
+
+        SpatialHash {
+            bucket_size,
+            buckets: HashMap::new(),
+            entities: HashMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    /// Gets the box of an entity.
+    pub fn get(&self, id: Id) -> Option<Range<D>> {
+        self.entities.get(&id).cloned()
+    }
+
+    /// Adds an entity, or moves it if it already exists.
+    pub fn insert(&mut self, id: Id, bounds: Range<D>) {
+        if !self.update(id, bounds) {
+            self.add_to_buckets(id, bounds);
+            self.entities.insert(id, bounds);
+        }
+    }
+
+    /// Moves an existing entity to a new box. Returns false if the entity doesn't exist.
+    pub fn update(&mut self, id: Id, bounds: Range<D>) -> bool {
+        let old_bounds = match self.entities.get_mut(&id) {
+            Some(old_bounds) => old_bounds,
+            None => return false,
+        };
+        let old_buckets = buckets_of(*old_bounds, self.bucket_size);
+        *old_bounds = bounds;
+
+        // Small movements usually stay within the same buckets, then there's nothing to update
+        if buckets_of(bounds, self.bucket_size) != old_buckets {
+            self.remove_from_buckets(id, old_buckets);
+            self.add_to_buckets(id, bounds);
+        }
+
+        true
+    }
+
+    /// Removes an entity, returning its box if it existed.
+    pub fn remove(&mut self, id: Id) -> Option<Range<D>> {
+        let bounds = self.entities.remove(&id)?;
+        self.remove_from_buckets(id, buckets_of(bounds, self.bucket_size));
+        Some(bounds)
+    }
+
+    /// Finds all entities with boxes overlapping a range.
+    pub fn query_range(&self, range: Range<D>) -> Vec<Id> {
+        self.query(range, |bounds| bounds.intersection(&range).is_some())
+    }
+
+    /// Finds all entities with boxes that have at least one position within `radius` of
+    /// `center`.
+    pub fn query_radius(&self, center: D::Point, radius: i32) -> Vec<Id> {
+        let range = Range::new(center, center).expand(radius);
+        let radius_squared = radius as i64 * radius as i64;
+
+        self.query(range, |bounds| {
+            // The closest position in the box to the center
+            let closest = D::min(D::max(center, bounds.start), bounds.end);
+            let offset = closest - center;
+            let distance_squared: i64 = D::axes().iter()
+                .map(|axis| D::component(offset, *axis) as i64)
+                .map(|v| v * v)
+                .sum();
+            distance_squared <= radius_squared
+        })
+    }
+
+    /// Finds every pair of entities with overlapping boxes, for use as a broad phase before
+    /// checking collisions more precisely. Every pair is only returned once.
+    pub fn pairs(&self) -> Vec<(Id, Id)> {
+        let mut pairs = Vec::new();
+
+        for (bucket, ids) in &self.buckets {
+            for (i, a) in ids.iter().enumerate() {
+                for b in &ids[i + 1..] {
+                    let overlap = match self.entities[a].intersection(&self.entities[b]) {
+                        Some(overlap) => overlap,
+                        None => continue,
+                    };
+
+                    // Pairs sharing multiple buckets are only reported by the bucket containing
+                    // the start of their overlap
+                    if D::to_chunk(overlap.start, self.bucket_size).0 == *bucket {
+                        pairs.push((*a, *b));
+                    }
+                }
+            }
+        }
+
+        pairs
+    }
+
+    fn query<F: FnMut(&Range<D>) -> bool>(&self, range: Range<D>, mut matches: F) -> Vec<Id> {
+        let mut seen = HashSet::new();
+        let mut found = Vec::new();
+
+        for bucket in buckets_of(range, self.bucket_size).iter() {
+            let ids = match self.buckets.get(&bucket) {
+                Some(ids) => ids,
+                None => continue,
+            };
+
+            for id in ids {
+                if seen.insert(*id) && matches(&self.entities[id]) {
+                    found.push(*id);
+                }
+            }
+        }
+
+        found
+    }
+
+    fn add_to_buckets(&mut self, id: Id, bounds: Range<D>) {
+        for bucket in buckets_of(bounds, self.bucket_size).iter() {
+            self.buckets.entry(bucket).or_default().push(id);
+        }
+    }
+
+    fn remove_from_buckets(&mut self, id: Id, buckets: Range<D>) {
+        for bucket in buckets.iter() {
+            let is_empty = match self.buckets.get_mut(&bucket) {
+                Some(ids) => {
+                    ids.retain(|other| *other != id);
+                    ids.is_empty()
+                },
+                None => false,
+            };
+
+            if is_empty {
+                self.buckets.remove(&bucket);
+            }
+        }
+    }
+}
+
+/// The range of buckets a box overlaps.
+fn buckets_of<D: Dim>(bounds: Range<D>, bucket_size: D::Vector) -> Range<D> {
+    Range::new(D::to_chunk(bounds.start, bucket_size).0, D::to_chunk(bounds.end, bucket_size).0)
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Vector2},
+
+        grid::{Dim2, Range, SpatialHash},
+    };
+
+    #[test]
+    fn queries_find_nearby_entities() {
+        let mut hash: SpatialHash<u32, Dim2> = SpatialHash::new(Vector2::new(16, 16));
+        hash.insert(1, Range::new_dim2(0, 0, 7, 7));
+        hash.insert(2, Range::new_dim2(-40, 10, -30, 50));
+        hash.insert(3, Range::new_dim2(100, 100, 101, 101));
+
+        let mut found = hash.query_range(Range::new_dim2(-35, 0, 5, 12));
+        found.sort();
+        assert_eq!(found, vec![1, 2]);
+
+        assert_eq!(hash.query_radius(Point2::new(10, 10), 5), vec![1]);
+        assert!(hash.query_radius(Point2::new(11, 11), 5).is_empty());
+
+        hash.update(3, Range::new_dim2(9, 9, 10, 10));
+        let mut found = hash.query_radius(Point2::new(10, 10), 5);
+        found.sort();
+        assert_eq!(found, vec![1, 3]);
+
+        assert_eq!(hash.remove(1), Some(Range::new_dim2(0, 0, 7, 7)));
+        assert_eq!(hash.query_radius(Point2::new(10, 10), 5), vec![3]);
+        assert_eq!(hash.len(), 2);
+        assert!(!hash.update(1, Range::new_dim2(0, 0, 1, 1)));
+    }
+
+    #[test]
+    fn pairs_are_found_once() {
+        let mut hash: SpatialHash<u32, Dim2> = SpatialHash::new(Vector2::new(8, 8));
+        hash.insert(1, Range::new_dim2(0, 0, 20, 20));
+        hash.insert(2, Range::new_dim2(5, 5, 30, 30));
+        hash.insert(3, Range::new_dim2(21, 21, 22, 22));
+        hash.insert(4, Range::new_dim2(-10, -10, -9, -9));
+
+        let mut pairs: Vec<_> = hash.pairs().into_iter()
+            .map(|(a, b)| if a < b { (a, b) } else { (b, a) })
+            .collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![(1, 2), (2, 3)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Bucket size must be larger than zero")]
+    fn negative_bucket_sizes_are_rejected() {
+        let _: SpatialHash<u32, Dim2> = SpatialHash::new(Vector2::new(-16, -16));
+    }
+}
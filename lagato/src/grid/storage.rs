@@ -0,0 +1,354 @@
+use {
+    std::convert::{TryFrom},
+};
+
+/// Backing storage for the cells of a `Grid`, indexed by `Dim::index`.
+/// Cells are accessed by value, which lets storages pack cells in ways that can't be referenced.
+pub trait Storage<Cell> {
+    fn filled(amount: usize, value: Cell) -> Self;
+
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn get(&self, index: usize) -> Cell;
+    fn set(&mut self, index: usize, value: Cell);
+}
+
+impl<Cell: Clone> Storage<Cell> for Vec<Cell> {
+    fn filled(amount: usize, value: Cell) -> Self {
+        vec![value; amount]
+    }
+
+    fn len(&self) -> usize {
+        Vec::len(self)
+    }
+
+    fn get(&self, index: usize) -> Cell {
+        self[index].clone()
+    }
+
+    fn set(&mut self, index: usize, value: Cell) {
+        self[index] = value;
+    }
+}
+
+/// Stores `bool` cells as single bits, using an eighth of the memory of a `Vec<bool>`.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(try_from = "RawBitStorage")]
+pub struct BitStorage {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl Storage<bool> for BitStorage {
+    fn filled(amount: usize, value: bool) -> Self {
+        let word = if value { !0 } else { 0 };
+        BitStorage {
+            words: vec![word; amount.div_ceil(64)],
+            len: amount,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, index: usize) -> bool {
+        assert!(index < self.len);
+        (self.words[index / 64] >> (index % 64)) & 1 == 1
+    }
+
+    fn set(&mut self, index: usize, value: bool) {
+        assert!(index < self.len);
+        let mask = 1 << (index % 64);
+        if value {
+            self.words[index / 64] |= mask;
+        } else {
+            self.words[index / 64] &= !mask;
+        }
+    }
+}
+
+/// Stores a table of the distinct values in use, and for every cell an index into that table
+/// packed into as few bits as needed. The index width grows as more distinct values are stored,
+/// a storage with only one value in use doesn't store any indices at all.
+#[derive(Deserialize, Serialize, Clone)]
+#[serde(try_from = "RawPaletteStorage<Cell>")]
+pub struct PaletteStorage<Cell> {
+    palette: Vec<Cell>,
+    /// How many cells use each palette entry, entries that are no longer used get reused.
+    counts: Vec<usize>,
+    indices: Vec<u64>,
+    bits: u32,
+    len: usize,
+}
+
+impl<Cell: Clone + PartialEq> PaletteStorage<Cell> {
+    /// The amount of distinct values that can be stored without growing the indices.
+    pub fn capacity(&self) -> usize {
+        1 << self.bits
+    }
+
+    /// The amount of bits currently used per cell.
+    pub fn bits_per_cell(&self) -> u32 {
+        self.bits
+    }
+
+    fn index(&self, index: usize) -> usize {
+        read_packed(&self.indices, self.bits, index)
+    }
+
+    fn set_index(&mut self, index: usize, palette_index: usize) {
+        write_packed(&mut self.indices, self.bits, index, palette_index)
+    }
+
+    fn find_or_add(&mut self, value: Cell) -> usize {
+        if let Some(index) = self.palette.iter().position(|v| *v == value) {
+            return index
+        }
+
+        // Reuse an entry nothing refers to anymore if we can
+        if let Some(index) = self.counts.iter().position(|c| *c == 0) {
+            self.palette[index] = value;
+            return index
+        }
+
+        if self.palette.len() == self.capacity() {
+            self.grow();
+        }
+
+        self.palette.push(value);
+        self.counts.push(0);
+        self.palette.len() - 1
+    }
+
+    fn grow(&mut self) {
+        let new_bits = if self.bits == 0 { 1 } else { self.bits * 2 };
+        let per_word = 64 / new_bits as usize;
+
+        let mut indices = vec![0; self.len.div_ceil(per_word)];
+        for i in 0..self.len {
+            write_packed(&mut indices, new_bits, i, self.index(i));
+        }
+
+        self.indices = indices;
+        self.bits = new_bits;
+    }
+}
+
+impl<Cell: Clone + PartialEq> Storage<Cell> for PaletteStorage<Cell> {
+    fn filled(amount: usize, value: Cell) -> Self {
+        PaletteStorage {
+            palette: vec![value],
+            counts: vec![amount],
+            indices: Vec::new(),
+            bits: 0,
+            len: amount,
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.len
+    }
+
+    fn get(&self, index: usize) -> Cell {
+        assert!(index < self.len);
+        self.palette[self.index(index)].clone()
+    }
+
+    fn set(&mut self, index: usize, value: Cell) {
+        assert!(index < self.len);
+
+        let old = self.index(index);
+        if self.palette[old] == value {
+            return
+        }
+        self.counts[old] -= 1;
+
+        let new = self.find_or_add(value);
+        self.counts[new] += 1;
+        self.set_index(index, new);
+    }
+}
+
+// Deserialized storages are checked before use, so corrupt data gives an error instead of a
+// panic on a later access
+
+#[derive(Deserialize)]
+struct RawBitStorage {
+    words: Vec<u64>,
+    len: usize,
+}
+
+impl TryFrom<RawBitStorage> for BitStorage {
+    type Error = &'static str;
+
+    fn try_from(raw: RawBitStorage) -> Result<Self, Self::Error> {
+        if raw.words.len() != raw.len.div_ceil(64) {
+            return Err("bit storage word count doesn't match its length")
+        }
+
+        Ok(BitStorage {
+            words: raw.words,
+            len: raw.len,
+        })
+    }
+}
+
+#[derive(Deserialize)]
+struct RawPaletteStorage<Cell> {
+    palette: Vec<Cell>,
+    counts: Vec<usize>,
+    indices: Vec<u64>,
+    bits: u32,
+    len: usize,
+}
+
+impl<Cell> TryFrom<RawPaletteStorage<Cell>> for PaletteStorage<Cell> {
+    type Error = &'static str;
+
+    fn try_from(raw: RawPaletteStorage<Cell>) -> Result<Self, Self::Error> {
+        if ![0, 1, 2, 4, 8, 16, 32].contains(&raw.bits) {
+            return Err("palette storage bits per cell doesn't divide 64 evenly")
+        }
+        if raw.palette.is_empty() || raw.palette.len() > 1 << raw.bits {
+            return Err("palette storage palette size doesn't fit its bits per cell")
+        }
+        if raw.counts.len() != raw.palette.len() {
+            return Err("palette storage counts don't match its palette")
+        }
+
+        let expected_words = if raw.bits == 0 { 0 } else {
+            raw.len.div_ceil(64 / raw.bits as usize)
+        };
+        if raw.indices.len() != expected_words {
+            return Err("palette storage index count doesn't match its length")
+        }
+
+        // The counts have to be exact, or changing cells would underflow them
+        let mut counts = vec![0; raw.palette.len()];
+        for i in 0..raw.len {
+            let index = read_packed(&raw.indices, raw.bits, i);
+            if index >= counts.len() {
+                return Err("palette storage index is outside of its palette")
+            }
+            counts[index] += 1;
+        }
+        if counts != raw.counts {
+            return Err("palette storage counts don't match its indices")
+        }
+
+        Ok(PaletteStorage {
+            palette: raw.palette,
+            counts: raw.counts,
+            indices: raw.indices,
+            bits: raw.bits,
+            len: raw.len,
+        })
+    }
+}
+
+// Packed values never straddle two words, the bit widths used all divide 64 evenly
+
+fn read_packed(words: &[u64], bits: u32, index: usize) -> usize {
+    if bits == 0 {
+        return 0
+    }
+
+    let per_word = 64 / bits as usize;
+    let shift = (index % per_word) * bits as usize;
+    ((words[index / per_word] >> shift) & mask(bits)) as usize
+}
+
+fn write_packed(words: &mut [u64], bits: u32, index: usize, value: usize) {
+    if bits == 0 {
+        return
+    }
+
+    let per_word = 64 / bits as usize;
+    let shift = (index % per_word) * bits as usize;
+    let word = &mut words[index / per_word];
+    *word &= !(mask(bits) << shift);
+    *word |= (value as u64) << shift;
+}
+
+fn mask(bits: u32) -> u64 {
+    if bits >= 64 { !0 } else { (1 << bits) - 1 }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        bincode,
+
+        grid::{Storage, BitStorage, PaletteStorage},
+    };
+
+    #[test]
+    fn bit_storage_stores_bits() {
+        let mut storage = BitStorage::filled(130, false);
+
+        storage.set(0, true);
+        storage.set(64, true);
+        storage.set(129, true);
+        storage.set(64, false);
+
+        let set: Vec<usize> = (0..130).filter(|i| storage.get(*i)).collect();
+        assert_eq!(set, vec![0, 129]);
+    }
+
+    #[test]
+    fn palette_storage_grows_and_keeps_values() {
+        let mut storage = PaletteStorage::filled(1000, 0u16);
+        assert_eq!(storage.bits_per_cell(), 0);
+
+        for i in 0..1000 {
+            storage.set(i, (i % 300) as u16);
+        }
+
+        assert_eq!(storage.bits_per_cell(), 16);
+        for i in 0..1000 {
+            assert_eq!(storage.get(i), (i % 300) as u16);
+        }
+    }
+
+    #[test]
+    fn palette_storage_reuses_unused_entries() {
+        let mut storage = PaletteStorage::filled(10, 'a');
+
+        storage.set(3, 'b');
+        storage.set(3, 'c');
+
+        // 'b' isn't used anymore so 'c' takes its place, and we still fit in one bit
+        assert_eq!(storage.bits_per_cell(), 1);
+        assert_eq!(storage.get(3), 'c');
+        assert_eq!(storage.get(4), 'a');
+    }
+
+    #[test]
+    fn deserializing_checks_storages() {
+        let mut storage = PaletteStorage::filled(100, 'a');
+        storage.set(7, 'b');
+        let data = bincode::serialize(&storage).unwrap();
+        let loaded: PaletteStorage<char> = bincode::deserialize(&data).unwrap();
+        assert_eq!(loaded.get(7), 'b');
+
+        // Claiming more cells than there are indices for
+        let mut corrupt = storage.clone();
+        corrupt.len = 1000;
+        let data = bincode::serialize(&corrupt).unwrap();
+        assert!(bincode::deserialize::<PaletteStorage<char>>(&data).is_err());
+
+        let mut corrupt = storage;
+        corrupt.bits = 3;
+        let data = bincode::serialize(&corrupt).unwrap();
+        assert!(bincode::deserialize::<PaletteStorage<char>>(&data).is_err());
+
+        let mut corrupt = BitStorage::filled(130, true);
+        corrupt.words.pop();
+        let data = bincode::serialize(&corrupt).unwrap();
+        assert!(bincode::deserialize::<BitStorage>(&data).is_err());
+    }
+}
@@ -0,0 +1,211 @@
+use {
+    grid::{Dim, Dim2, Dim3, Axis, Grid, Range, Storage, Error},
+};
+
+/// Where existing cells end up along every axis when resizing a grid.
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum Anchor {
+    Start,
+    Center,
+    End,
+}
+
+impl<Cell, D: Dim, S: Storage<Cell>> Grid<Cell, D, S> {
+    /// Mirrors the grid in place, flipping the positions of cells along an axis.
+    pub fn mirror(&mut self, axis: Axis) {
+        let size = self.size();
+        let length = D::component(size, axis);
+
+        // Swap every cell in the first half with its mirrored cell in the second half
+        let half = Range::<D>::new(D::start(), D::end(D::with_component(size, axis, length / 2)));
+        for position in half.iter() {
+            let offset = position - D::start();
+            let mirrored_offset = D::with_component(
+                offset, axis, length - 1 - D::component(offset, axis),
+            );
+            let mirrored = D::start() + mirrored_offset;
+
+            let value = self.value(position).unwrap();
+            let mirrored_value = self.value(mirrored).unwrap();
+            self.set(position, mirrored_value).unwrap();
+            self.set(mirrored, value).unwrap();
+        }
+    }
+
+    /// Rotates the grid in place by quarter turns, turning `from` towards `to`.
+    fn rotate_in_plane(&mut self, from: Axis, to: Axis, turns: i32) {
+        let turns = turns.rem_euclid(4);
+        if turns == 0 {
+            return
+        }
+
+        let size = self.size();
+        let (size_from, size_to) = (D::component(size, from), D::component(size, to));
+        let new_size = if turns == 2 {
+            size
+        } else {
+            D::with_component(D::with_component(size, from, size_to), to, size_from)
+        };
+
+        self.permute(new_size, |position| {
+            let offset = position - D::start();
+            let (a, b) = (D::component(offset, from), D::component(offset, to));
+            let (new_a, new_b) = match turns {
+                1 => (size_to - 1 - b, a),
+                2 => (size_from - 1 - a, size_to - 1 - b),
+                _ => (b, size_from - 1 - a),
+            };
+
+            D::start() + D::with_component(D::with_component(offset, from, new_a), to, new_b)
+        });
+    }
+}
+
+impl<Cell: Clone, D: Dim, S: Storage<Cell>> Grid<Cell, D, S> {
+    /// Creates a resized copy of this grid, with the existing cells placed according to the
+    /// anchor and any new cells set to `fill`. Cells that don't fit in the new size are dropped.
+    /// Returns `Error::SizeMismatch` if the size isn't valid for the dimension.
+    pub fn resized(&self, size: D::Vector, anchor: Anchor, fill: Cell) -> Result<Self, Error> {
+        if !D::is_valid_size(size) {
+            return Err(Error::SizeMismatch)
+        }
+
+        let mut offset = D::splat(0);
+        for axis in D::axes() {
+            let difference = D::component(size, *axis) - D::component(self.size(), *axis);
+            let axis_offset = match anchor {
+                Anchor::Start => 0,
+                Anchor::Center => difference / 2,
+                Anchor::End => difference,
+            };
+            offset = D::with_component(offset, *axis, axis_offset);
+        }
+
+        let mut grid = Grid::filled(size, fill);
+        grid.paste(self, offset);
+        Ok(grid)
+    }
+
+    /// Creates a copy of the part of this grid within the range. Parts of the range outside of
+    /// the grid are ignored, if nothing is left an error is returned. The size of what's left
+    /// has to be valid for the dimension, like with `copy_region`.
+    pub fn cropped(&self, range: Range<D>) -> Result<Self, Error> {
+        match range.intersection(&self.range()) {
+            Some(range) => self.copy_region(range),
+            None => Err(Error::OutOfBounds),
+        }
+    }
+}
+
+impl<Cell, S: Storage<Cell>> Grid<Cell, Dim2, S> {
+    /// Rotates the grid in place by quarter turns, counter-clockwise when Y points up.
+    /// Negative turns rotate clockwise.
+    pub fn rotate(&mut self, turns: i32) {
+        self.rotate_in_plane(Axis::X, Axis::Y, turns);
+    }
+}
+
+impl<Cell, S: Storage<Cell>> Grid<Cell, Dim3, S> {
+    /// Rotates the grid in place by quarter turns around an axis, following the right-hand
+    /// rule. Negative turns rotate the other way.
+    pub fn rotate(&mut self, axis: Axis, turns: i32) {
+        match axis {
+            Axis::X => self.rotate_in_plane(Axis::Y, Axis::Z, turns),
+            Axis::Y => self.rotate_in_plane(Axis::Z, Axis::X, turns),
+            Axis::Z => self.rotate_in_plane(Axis::X, Axis::Y, turns),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Point3, Vector2, Vector3},
+
+        grid::{Grid, Tiles, Voxels, MortonVoxels, Range, Axis, Anchor, Error},
+    };
+
+    fn numbered(width: i32, height: i32) -> Tiles<i32> {
+        let mut tiles: Tiles<i32> = Grid::empty(Vector2::new(width, height));
+        for position in tiles.iter_pos() {
+            *tiles.get_mut(position).unwrap() = position.x + position.y * 10;
+        }
+        tiles
+    }
+
+    #[test]
+    fn rotating_moves_cells_and_swaps_size() {
+        let mut tiles = numbered(3, 2);
+
+        tiles.rotate(1);
+        assert_eq!(tiles.size(), Vector2::new(2, 3));
+        // The bottom right corner ends up top right
+        assert_eq!(*tiles.get(Point2::new(1, 2)).unwrap(), 2);
+        assert_eq!(*tiles.get(Point2::new(1, 0)).unwrap(), 0);
+
+        tiles.rotate(-1);
+        assert_eq!(tiles.get(Point2::new(0, 0)), numbered(3, 2).get(Point2::new(0, 0)));
+        tiles.rotate(6);
+        assert_eq!(*tiles.get(Point2::new(0, 0)).unwrap(), 12);
+        tiles.rotate(2);
+        for position in tiles.iter_pos() {
+            assert_eq!(*tiles.get(position).unwrap(), position.x + position.y * 10);
+        }
+
+        let mut voxels: Voxels<u8> = Grid::empty(Vector3::new(2, 3, 4));
+        *voxels.get_mut(Point3::new(0, 0, 3)).unwrap() = 1;
+        voxels.rotate(Axis::Y, 1);
+        assert_eq!(voxels.size(), Vector3::new(4, 3, 2));
+        assert_eq!(*voxels.get(Point3::new(3, 0, 1)).unwrap(), 1);
+    }
+
+    #[test]
+    fn mirroring_flips_along_axis() {
+        let mut tiles = numbered(3, 3);
+
+        tiles.mirror(Axis::X);
+        assert_eq!(*tiles.get(Point2::new(0, 1)).unwrap(), 12);
+        assert_eq!(*tiles.get(Point2::new(1, 1)).unwrap(), 11);
+
+        tiles.mirror(Axis::Y);
+        assert_eq!(*tiles.get(Point2::new(0, 0)).unwrap(), 22);
+    }
+
+    #[test]
+    fn resizing_and_cropping_keep_cells() {
+        let tiles = numbered(3, 3);
+
+        let grown = tiles.resized(Vector2::new(5, 6), Anchor::Center, -1).unwrap();
+        assert_eq!(*grown.get(Point2::new(0, 0)).unwrap(), -1);
+        assert_eq!(*grown.get(Point2::new(1, 1)).unwrap(), 0);
+        assert_eq!(*grown.get(Point2::new(3, 3)).unwrap(), 22);
+
+        let shrunk = tiles.resized(Vector2::new(2, 2), Anchor::End, -1).unwrap();
+        assert_eq!(*shrunk.get(Point2::new(0, 0)).unwrap(), 11);
+
+        let cropped = tiles.cropped(Range::new_dim2(1, 2, 5, 5)).unwrap();
+        assert_eq!(cropped.size(), Vector2::new(2, 1));
+        assert_eq!(*cropped.get(Point2::new(0, 0)).unwrap(), 21);
+        assert_eq!(tiles.cropped(Range::new_dim2(4, 4, 5, 5)).err(), Some(Error::OutOfBounds));
+    }
+
+    #[test]
+    fn resizing_and_cropping_reject_invalid_morton_sizes() {
+        let mut voxels: MortonVoxels<u8> = Grid::empty(Vector3::new(4, 4, 4));
+        *voxels.get_mut(Point3::new(1, 2, 3)).unwrap() = 7;
+
+        let grown = voxels.resized(Vector3::new(8, 4, 4), Anchor::End, 0).unwrap();
+        assert_eq!(*grown.get(Point3::new(5, 2, 3)).unwrap(), 7);
+        assert_eq!(
+            voxels.resized(Vector3::new(5, 4, 4), Anchor::Start, 0).err(),
+            Some(Error::SizeMismatch),
+        );
+
+        let cropped = voxels.cropped(Range::new(Point3::new(0, 2, 2), Point3::new(1, 3, 3)));
+        assert_eq!(*cropped.unwrap().get(Point3::new(1, 0, 1)).unwrap(), 7);
+        assert_eq!(
+            voxels.cropped(Range::new(Point3::new(0, 0, 0), Point3::new(2, 9, 9))).err(),
+            Some(Error::SizeMismatch),
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use {
+    cgmath::{Point2},
+
+    grid::{Grid, Dim2},
+};
+
+impl<Cell, S> Grid<Cell, Dim2, S> {
+    /// Calculates which positions can be seen from `origin` within `radius`, using recursive
+    /// shadowcasting. Opaque positions themselves are visible, but block the view of anything
+    /// behind them. Positions outside of the grid are treated as opaque.
+    pub fn field_of_view<F: FnMut(Point2<i32>) -> bool>(
+        &self, origin: Point2<i32>, radius: i32, mut is_opaque: F,
+    ) -> Grid<bool, Dim2> {
+        let mut visible: Grid<bool, Dim2> = Grid::empty(self.size());
+        if !self.is_in_bounds(origin) {
+            return visible
+        }
+        *visible.get_mut(origin).unwrap() = true;
+
+        // Transforms from octant-local coordinates to grid coordinates, as xx, xy, yx and yy
+        const OCTANTS: [[i32; 4]; 8] = [
+            [1, 0, 0, 1],
+            [0, 1, 1, 0],
+            [0, -1, 1, 0],
+            [-1, 0, 0, 1],
+            [-1, 0, 0, -1],
+            [0, -1, -1, 0],
+            [0, 1, -1, 0],
+            [1, 0, 0, -1],
+        ];
+
+        for octant in &OCTANTS {
+            let mut caster = ShadowCaster {
+                visible: &mut visible,
+                is_opaque: &mut is_opaque,
+                origin,
+                radius,
+                xx: octant[0],
+                xy: octant[1],
+                yx: octant[2],
+                yy: octant[3],
+            };
+            caster.cast_light(1, 1.0, 0.0);
+        }
+
+        visible
+    }
+
+    /// Checks if nothing opaque is between two positions, the positions themselves are allowed
+    /// to be opaque. Positions outside of the grid are treated as opaque.
+    pub fn line_of_sight<F: FnMut(Point2<i32>) -> bool>(
+        &self, from: Point2<i32>, to: Point2<i32>, mut is_opaque: F,
+    ) -> bool {
+        if !self.is_in_bounds(from) || !self.is_in_bounds(to) {
+            return false
+        }
+
+        Line::new(from, to)
+            .filter(|p| *p != from && *p != to)
+            .all(|p| self.is_in_bounds(p) && !is_opaque(p))
+    }
+}
+
+/// Scans a single octant, with the octant's transform to grid coordinates.
+struct ShadowCaster<'a, F: 'a> {
+    visible: &'a mut Grid<bool, Dim2>,
+    is_opaque: &'a mut F,
+    origin: Point2<i32>,
+    radius: i32,
+    xx: i32,
+    xy: i32,
+    yx: i32,
+    yy: i32,
+}
+
+impl<'a, F: FnMut(Point2<i32>) -> bool> ShadowCaster<'a, F> {
+    fn cast_light(&mut self, row: i32, mut start_slope: f32, end_slope: f32) {
+        if start_slope < end_slope {
+            return
+        }
+
+        let radius = self.radius;
+        let radius_squared = radius * radius;
+        let mut next_start_slope = start_slope;
+
+        for distance in row..radius + 1 {
+            let dy = -distance;
+            let mut blocked = false;
+
+            for dx in -distance..1 {
+                let left_slope = (dx as f32 - 0.5) / (dy as f32 + 0.5);
+                let right_slope = (dx as f32 + 0.5) / (dy as f32 - 0.5);
+
+                if start_slope < right_slope {
+                    continue
+                }
+                if end_slope > left_slope {
+                    break
+                }
+
+                let position = Point2::new(
+                    self.origin.x + dx * self.xx + dy * self.xy,
+                    self.origin.y + dx * self.yx + dy * self.yy,
+                );
+                let in_bounds = self.visible.is_in_bounds(position);
+
+                if in_bounds && dx * dx + dy * dy <= radius_squared {
+                    *self.visible.get_mut(position).unwrap() = true;
+                }
+
+                let opaque = !in_bounds || (self.is_opaque)(position);
+                if blocked {
+                    if opaque {
+                        // Still in the shadow, move its start along
+                        next_start_slope = right_slope;
+                    } else {
+                        blocked = false;
+                        start_slope = next_start_slope;
+                    }
+                } else if opaque && distance < radius {
+                    // Started a shadow, scan what's visible before it in the next row
+                    blocked = true;
+                    self.cast_light(distance + 1, start_slope, left_slope);
+                    next_start_slope = right_slope;
+                }
+            }
+
+            if blocked {
+                break
+            }
+        }
+    }
+}
+
+/// Iterates over the positions on a line between two positions using Bresenham's algorithm,
+/// including both ends.
+pub struct Line {
+    current: Point2<i32>,
+    end: Point2<i32>,
+    delta_x: i32,
+    delta_y: i32,
+    step_x: i32,
+    step_y: i32,
+    error: i32,
+    done: bool,
+}
+
+impl Line {
+    pub fn new(start: Point2<i32>, end: Point2<i32>) -> Self {
+        let delta_x = (end.x - start.x).abs();
+        let delta_y = -(end.y - start.y).abs();
+
+        Line {
+            current: start,
+            end,
+            delta_x,
+            delta_y,
+            step_x: if start.x < end.x { 1 } else { -1 },
+            step_y: if start.y < end.y { 1 } else { -1 },
+            error: delta_x + delta_y,
+            done: false,
+        }
+    }
+}
+
+impl Iterator for Line {
+    type Item = Point2<i32>;
+
+    fn next(&mut self) -> Option<Point2<i32>> {
+        if self.done {
+            return None
+        }
+
+        let position = self.current;
+        if position == self.end {
+            self.done = true;
+        } else {
+            let error2 = 2 * self.error;
+            if error2 >= self.delta_y {
+                self.error += self.delta_y;
+                self.current.x += self.step_x;
+            }
+            if error2 <= self.delta_x {
+                self.error += self.delta_x;
+                self.current.y += self.step_y;
+            }
+        }
+
+        Some(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Vector2},
+
+        grid::{Grid, Tiles, Line},
+    };
+
+    #[test]
+    fn line_includes_both_ends() {
+        let line: Vec<_> = Line::new(Point2::new(0, 0), Point2::new(4, -2)).collect();
+
+        assert_eq!(line.len(), 5);
+        assert_eq!(line.first(), Some(&Point2::new(0, 0)));
+        assert_eq!(line.last(), Some(&Point2::new(4, -2)));
+    }
+
+    #[test]
+    fn walls_block_view() {
+        let mut tiles: Tiles<bool> = Grid::empty(Vector2::new(11, 11));
+        *tiles.get_mut(Point2::new(7, 5)).unwrap() = true;
+        let is_opaque = |p| *tiles.get(p).unwrap();
+
+        let visible = tiles.field_of_view(Point2::new(5, 5), 4, is_opaque);
+
+        assert!(*visible.get(Point2::new(5, 5)).unwrap());
+        assert!(*visible.get(Point2::new(6, 5)).unwrap());
+        assert!(*visible.get(Point2::new(7, 5)).unwrap());
+        assert!(!*visible.get(Point2::new(8, 5)).unwrap());
+        assert!(*visible.get(Point2::new(5, 9)).unwrap());
+        assert!(!*visible.get(Point2::new(5, 10)).unwrap());
+        assert!(!*visible.get(Point2::new(9, 9)).unwrap());
+
+        assert!(tiles.line_of_sight(Point2::new(5, 5), Point2::new(7, 5), is_opaque));
+        assert!(!tiles.line_of_sight(Point2::new(5, 5), Point2::new(9, 5), is_opaque));
+        assert!(tiles.line_of_sight(Point2::new(5, 5), Point2::new(9, 8), is_opaque));
+    }
+}
@@ -1,9 +1,14 @@
+extern crate bincode;
+extern crate byteorder;
 extern crate cgmath;
+#[cfg(feature = "parallel")] extern crate rayon;
 extern crate serde;
 #[macro_use] extern crate serde_derive;
 
 pub mod camera;
 pub mod grid;
+pub mod noise;
+pub mod pathfinding;
 mod event;
 
 pub use self::{
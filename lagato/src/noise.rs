@@ -0,0 +1,273 @@
+//! Seedable gradient noise and helpers for generating terrain with it. The same seed always
+//! generates the same noise, so worlds can be regenerated from just their seed.
+
+use {
+    cgmath::{Point2, Point3, Vector2, Vector3},
+
+    grid::{Grid, Tiles, Voxels},
+};
+
+/// Improved Perlin gradient noise, in 2D and 3D.
+pub struct Perlin {
+    /// A shuffled permutation of 0 to 255, repeated twice to avoid wrapping indices.
+    permutation: Vec<u8>,
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut values: Vec<u8> = (0..256).map(|v| v as u8).collect();
+
+        // Fisher-Yates, with our own random numbers so the result never changes
+        let mut state = seed;
+        for i in (1..values.len()).rev() {
+            let j = (split_mix(&mut state) % (i as u64 + 1)) as usize;
+            values.swap(i, j);
+        }
+
+        let mut permutation = values.clone();
+        permutation.extend(values);
+        Perlin {
+            permutation,
+        }
+    }
+
+    /// Samples 2D noise, roughly ranging from -1 to 1. Integer positions always give 0.
+    pub fn get2(&self, position: Point2<f32>) -> f32 {
+        let (x, y) = (position.x.floor(), position.y.floor());
+        let (xi, yi) = (x as i32 & 255, y as i32 & 255);
+        let (xf, yf) = (position.x - x, position.y - y);
+        let (u, v) = (fade(xf), fade(yf));
+
+        let p = &self.permutation;
+        let hash = |x: i32, y: i32| p[p[x as usize] as usize + y as usize];
+
+        let a = lerp(u, grad2(hash(xi, yi), xf, yf), grad2(hash(xi + 1, yi), xf - 1.0, yf));
+        let b = lerp(
+            u,
+            grad2(hash(xi, yi + 1), xf, yf - 1.0),
+            grad2(hash(xi + 1, yi + 1), xf - 1.0, yf - 1.0),
+        );
+        lerp(v, a, b)
+    }
+
+    /// Samples 3D noise, roughly ranging from -1 to 1. Integer positions always give 0.
+    pub fn get3(&self, position: Point3<f32>) -> f32 {
+        let (x, y, z) = (position.x.floor(), position.y.floor(), position.z.floor());
+        let (xi, yi, zi) = (x as i32 & 255, y as i32 & 255, z as i32 & 255);
+        let (xf, yf, zf) = (position.x - x, position.y - y, position.z - z);
+        let (u, v, w) = (fade(xf), fade(yf), fade(zf));
+
+        let p = &self.permutation;
+        let hash = |x: i32, y: i32, z: i32| {
+            p[p[p[x as usize] as usize + y as usize] as usize + z as usize]
+        };
+        let corner = |dx: i32, dy: i32, dz: i32| {
+            grad3(
+                hash(xi + dx, yi + dy, zi + dz),
+                xf - dx as f32, yf - dy as f32, zf - dz as f32,
+            )
+        };
+
+        let x00 = lerp(u, corner(0, 0, 0), corner(1, 0, 0));
+        let x10 = lerp(u, corner(0, 1, 0), corner(1, 1, 0));
+        let x01 = lerp(u, corner(0, 0, 1), corner(1, 0, 1));
+        let x11 = lerp(u, corner(0, 1, 1), corner(1, 1, 1));
+        lerp(w, lerp(v, x00, x10), lerp(v, x01, x11))
+    }
+}
+
+/// Settings for layering multiple octaves of noise, each at a higher frequency and lower
+/// amplitude than the last.
+#[derive(Clone, Copy, Debug)]
+pub struct Fractal {
+    pub octaves: u32,
+    /// The frequency of the first octave, in noise periods per cell.
+    pub frequency: f32,
+    /// How much the frequency is multiplied by every octave.
+    pub lacunarity: f32,
+    /// How much the amplitude is multiplied by every octave.
+    pub persistence: f32,
+}
+
+impl Fractal {
+    pub fn new(octaves: u32, frequency: f32) -> Self {
+        Fractal {
+            octaves,
+            frequency,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        }
+    }
+
+    /// Fractal Brownian motion, roughly ranging from -1 to 1.
+    pub fn fbm2(&self, noise: &Perlin, position: Point2<f32>) -> f32 {
+        self.layer(|frequency| noise.get2(position * frequency))
+    }
+
+    /// Fractal Brownian motion, roughly ranging from -1 to 1.
+    pub fn fbm3(&self, noise: &Perlin, position: Point3<f32>) -> f32 {
+        self.layer(|frequency| noise.get3(position * frequency))
+    }
+
+    /// Ridged noise, ranging from 0 to 1, with sharp ridges where the noise crosses zero.
+    pub fn ridged2(&self, noise: &Perlin, position: Point2<f32>) -> f32 {
+        self.layer(|frequency| ridge(noise.get2(position * frequency)))
+    }
+
+    /// Ridged noise, ranging from 0 to 1, with sharp ridges where the noise crosses zero.
+    pub fn ridged3(&self, noise: &Perlin, position: Point3<f32>) -> f32 {
+        self.layer(|frequency| ridge(noise.get3(position * frequency)))
+    }
+
+    fn layer<F: FnMut(f32) -> f32>(&self, mut octave: F) -> f32 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut total = 0.0;
+        let mut total_amplitude = 0.0;
+
+        for _ in 0..self.octaves {
+            total += octave(frequency) * amplitude;
+            total_amplitude += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.persistence;
+        }
+
+        if total_amplitude > 0.0 { total / total_amplitude } else { 0.0 }
+    }
+}
+
+/// Generates a heightmap from fractal Brownian motion, with heights from 0 to 1.
+pub fn heightmap(noise: &Perlin, fractal: &Fractal, size: Vector2<i32>) -> Tiles<f32> {
+    let mut tiles: Tiles<f32> = Grid::empty(size);
+    let range = tiles.range();
+    tiles.fill_with(range, |p| {
+        let value = fractal.fbm2(noise, Point2::new(p.x as f32, p.y as f32));
+        (value * 0.5 + 0.5).clamp(0.0, 1.0)
+    });
+    tiles
+}
+
+/// Converts a heightmap with heights from 0 to 1 into solid columns of voxels, with a height
+/// of 1 filling the entire height of the voxels. The heightmap's Y axis becomes the Z axis of
+/// the voxels.
+pub fn heightmap_to_voxels(heightmap: &Tiles<f32>, height: i32) -> Voxels<bool> {
+    heightmap.to_voxels(height, |_| true)
+}
+
+/// Generates solid voxels with caves carved out wherever 3D noise is above `threshold`.
+/// Thresholds closer to 1 give fewer and smaller caves.
+pub fn caves(
+    noise: &Perlin, fractal: &Fractal, size: Vector3<i32>, threshold: f32,
+) -> Voxels<bool> {
+    let mut voxels: Voxels<bool> = Grid::empty(size);
+    let range = voxels.range();
+    voxels.fill_with(range, |p| {
+        fractal.fbm3(noise, Point3::new(p.x as f32, p.y as f32, p.z as f32)) <= threshold
+    });
+    voxels
+}
+
+pub(crate) fn split_mix(state: &mut u64) -> u64 {
+    *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    let mut value = *state;
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(t: f32, a: f32, b: f32) -> f32 {
+    a + t * (b - a)
+}
+
+fn ridge(value: f32) -> f32 {
+    let value = 1.0 - value.abs();
+    value * value
+}
+
+fn grad2(hash: u8, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
+
+fn grad3(hash: u8, x: f32, y: f32, z: f32) -> f32 {
+    // The 12 edges of a cube, with 4 repeated to get 16 cases
+    match hash & 15 {
+        0 | 12 => x + y,
+        1 | 14 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x + z,
+        5 => -x + z,
+        6 => x - z,
+        7 => -x - z,
+        8 => y + z,
+        9 | 13 => -y + z,
+        10 => y - z,
+        _ => -y - z,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Point3, Vector2, Vector3},
+
+        noise::{Perlin, Fractal, heightmap, heightmap_to_voxels, caves},
+    };
+
+    #[test]
+    fn noise_is_deterministic_per_seed() {
+        let a = Perlin::new(42);
+        let b = Perlin::new(42);
+        let c = Perlin::new(43);
+        let fractal = Fractal::new(4, 0.05);
+
+        let mut differs = false;
+        for i in 0..100 {
+            let position = Point3::new(i as f32 * 0.37, i as f32 * 0.11, -(i as f32) * 0.53);
+            assert_eq!(a.get3(position), b.get3(position));
+            differs |= a.get3(position) != c.get3(position);
+
+            let fbm = fractal.fbm3(&a, position);
+            let ridged = fractal.ridged2(&a, Point2::new(position.x, position.y));
+            assert!((-1.0..=1.0).contains(&fbm));
+            assert!((0.0..=1.0).contains(&ridged));
+        }
+        assert!(differs);
+
+        assert_eq!(a.get2(Point2::new(3.0, -7.0)), 0.0);
+    }
+
+    #[test]
+    fn generators_fill_grids() {
+        let noise = Perlin::new(7);
+        let fractal = Fractal::new(3, 0.1);
+
+        let heights = heightmap(&noise, &fractal, Vector2::new(16, 16));
+        assert!(heights.iter_pos().all(|p| (0.0..=1.0).contains(heights.get(p).unwrap())));
+        let smaller = heightmap(&noise, &fractal, Vector2::new(8, 8));
+        assert_eq!(heights.get(Point2::new(3, 4)), smaller.get(Point2::new(3, 4)));
+
+        let voxels = heightmap_to_voxels(&heights, 10);
+        assert_eq!(voxels.size(), Vector3::new(16, 10, 16));
+        let column = (0..10).filter(|y| *voxels.get(Point3::new(5, *y, 9)).unwrap()).count();
+        let expected = (*heights.get(Point2::new(5, 9)).unwrap() * 10.0).round() as usize;
+        assert_eq!(column, expected);
+
+        let carved = caves(&noise, &fractal, Vector3::new(16, 16, 16), 0.1);
+        let solid = carved.iter_pos().filter(|p| *carved.get(*p).unwrap()).count();
+        assert!(solid > 0 && solid < 16 * 16 * 16);
+    }
+}
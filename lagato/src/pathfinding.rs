@@ -0,0 +1,292 @@
+//! Pathfinding over `Grid`s. Movement costs are given by a closure receiving the position moved
+//! from and the position moved to, returning `None` if that move isn't possible.
+
+use {
+    std::{
+        cmp::{Ordering},
+        collections::{BinaryHeap},
+    },
+
+    grid::{Grid, Dim, Connectivity},
+};
+
+pub struct Path<D: Dim> {
+    /// The positions along the path, including the start and the end.
+    pub points: Vec<D::Point>,
+    /// The total cost of moving along the path.
+    pub cost: f32,
+    /// If false, the goal couldn't be reached and the path ends at the reachable position
+    /// closest to the goal instead.
+    pub complete: bool,
+}
+
+/// Finds the cheapest path from `start` to `goal` using A*.
+/// The heuristic used is the minimum amount of steps between two positions, so every step should
+/// cost at least 1 for the path to be the cheapest one. If the goal can't be reached, a partial
+/// path to the closest reachable position is returned. If the start is out of bounds, the path
+/// is empty.
+pub fn astar<Cell, D, S, F>(
+    grid: &Grid<Cell, D, S>, start: D::Point, goal: D::Point,
+    connectivity: Connectivity, mut cost: F,
+) -> Path<D> where
+    D: Dim,
+    F: FnMut(D::Point, D::Point) -> Option<f32>,
+{
+    if !grid.is_in_bounds(start) {
+        return Path { points: Vec::new(), cost: 0.0, complete: false }
+    }
+
+    let offsets = D::neighbor_offsets(connectivity);
+    let heuristic = |position| D::distance(position, goal, connectivity) as f32;
+
+    let mut costs: Grid<f32, D> = Grid::filled(grid.size(), f32::INFINITY);
+    let mut came_from: Grid<Option<D::Point>, D> = Grid::empty(grid.size());
+    let mut open = BinaryHeap::new();
+
+    *costs.get_mut(start).unwrap() = 0.0;
+    open.push(Node { priority: heuristic(start), position: start });
+
+    // Keep track of what's closest to the goal in case we can't reach it
+    let mut closest = (heuristic(start), 0.0, start);
+
+    while let Some(Node { priority, position }) = open.pop() {
+        let current_cost = *costs.get(position).unwrap();
+
+        // This node was already reached more cheaply through another path
+        if priority > current_cost + heuristic(position) {
+            continue
+        }
+
+        if position == goal {
+            return build_path(&came_from, goal, current_cost, true)
+        }
+
+        let distance = heuristic(position);
+        if distance < closest.0 || (distance == closest.0 && current_cost < closest.1) {
+            closest = (distance, current_cost, position);
+        }
+
+        for offset in &offsets {
+            let neighbor = position + *offset;
+            if !grid.is_in_bounds(neighbor) { continue }
+
+            let step_cost = match cost(position, neighbor) {
+                Some(step_cost) => step_cost,
+                None => continue,
+            };
+
+            let new_cost = current_cost + step_cost;
+            let neighbor_cost = costs.get_mut(neighbor).unwrap();
+            if new_cost < *neighbor_cost {
+                *neighbor_cost = new_cost;
+                *came_from.get_mut(neighbor).unwrap() = Some(position);
+                open.push(Node { priority: new_cost + heuristic(neighbor), position: neighbor });
+            }
+        }
+    }
+
+    build_path(&came_from, closest.2, closest.1, false)
+}
+
+/// Calculates the cheapest way to get to the nearest goal from every position in the grid using
+/// Dijkstra's algorithm.
+pub fn dijkstra<Cell, D, S, F>(
+    grid: &Grid<Cell, D, S>, goals: &[D::Point], connectivity: Connectivity, mut cost: F,
+) -> FlowField<D> where
+    D: Dim,
+    F: FnMut(D::Point, D::Point) -> Option<f32>,
+{
+    let offsets = D::neighbor_offsets(connectivity);
+
+    let mut costs: Grid<f32, D> = Grid::filled(grid.size(), f32::INFINITY);
+    let mut next: Grid<Option<D::Point>, D> = Grid::empty(grid.size());
+    let mut open = BinaryHeap::new();
+
+    for goal in goals {
+        if let Ok(goal_cost) = costs.get_mut(*goal) {
+            *goal_cost = 0.0;
+            open.push(Node { priority: 0.0, position: *goal });
+        }
+    }
+
+    while let Some(Node { priority, position }) = open.pop() {
+        let current_cost = *costs.get(position).unwrap();
+        if priority > current_cost {
+            continue
+        }
+
+        // We're searching backwards from the goals, so we're looking for the cost of moving from
+        // the neighbor to this position
+        for offset in &offsets {
+            let neighbor = position - *offset;
+            if !grid.is_in_bounds(neighbor) { continue }
+
+            let step_cost = match cost(neighbor, position) {
+                Some(step_cost) => step_cost,
+                None => continue,
+            };
+
+            let new_cost = current_cost + step_cost;
+            let neighbor_cost = costs.get_mut(neighbor).unwrap();
+            if new_cost < *neighbor_cost {
+                *neighbor_cost = new_cost;
+                *next.get_mut(neighbor).unwrap() = Some(position);
+                open.push(Node { priority: new_cost, position: neighbor });
+            }
+        }
+    }
+
+    FlowField {
+        costs,
+        next,
+    }
+}
+
+/// The result of `dijkstra`, telling for every position how to get to the nearest goal.
+pub struct FlowField<D: Dim> {
+    costs: Grid<f32, D>,
+    next: Grid<Option<D::Point>, D>,
+}
+
+impl<D: Dim> FlowField<D> {
+    /// The cost of getting from a position to the nearest goal, or `None` if no goal can be
+    /// reached from it.
+    pub fn cost(&self, position: D::Point) -> Option<f32> {
+        match self.costs.get(position) {
+            Ok(cost) if *cost != f32::INFINITY => Some(*cost),
+            _ => None,
+        }
+    }
+
+    /// The position to move to from a position to get closer to the nearest goal, or `None` if
+    /// the position is a goal or no goal can be reached from it.
+    pub fn next_step(&self, position: D::Point) -> Option<D::Point> {
+        self.next.get(position).ok().and_then(|next| *next)
+    }
+
+    /// Follows the flow field from a position to the nearest goal. If no goal can be reached, the
+    /// path is empty.
+    pub fn path_from(&self, position: D::Point) -> Vec<D::Point> {
+        if self.cost(position).is_none() {
+            return Vec::new()
+        }
+
+        let mut points = vec![position];
+        let mut current = position;
+        while let Some(next) = self.next_step(current) {
+            points.push(next);
+            current = next;
+        }
+
+        points
+    }
+}
+
+fn build_path<D: Dim>(
+    came_from: &Grid<Option<D::Point>, D>, end: D::Point, cost: f32, complete: bool,
+) -> Path<D> {
+    let mut points = vec![end];
+    let mut current = end;
+    while let Some(previous) = *came_from.get(current).unwrap() {
+        points.push(previous);
+        current = previous;
+    }
+    points.reverse();
+
+    Path {
+        points,
+        cost,
+        complete,
+    }
+}
+
+/// Entry in the open set, ordered so the lowest priority comes out of the heap first.
+struct Node<P> {
+    priority: f32,
+    position: P,
+}
+
+impl<P> PartialEq for Node<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.priority == other.priority
+    }
+}
+
+impl<P> Eq for Node<P> {}
+
+impl<P> PartialOrd for Node<P> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<P> Ord for Node<P> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.priority.partial_cmp(&self.priority).unwrap_or(Ordering::Equal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        cgmath::{Point2, Vector2, Point3, Vector3},
+
+        grid::{Grid, Tiles, Voxels, Connectivity, Range},
+        pathfinding::{astar, dijkstra},
+    };
+
+    fn walled_tiles() -> Tiles<bool> {
+        // A wall down the middle with a single gap at the bottom
+        let mut tiles: Tiles<bool> = Grid::empty(Vector2::new(5, 5));
+        tiles.fill(Range::new_dim2(2, 0, 2, 3), true);
+        tiles
+    }
+
+    #[test]
+    fn astar_goes_around_walls() {
+        let tiles = walled_tiles();
+        let walkable = |_, to| if *tiles.get(to).unwrap() { None } else { Some(1.0) };
+
+        let path = astar(
+            &tiles, Point2::new(0, 0), Point2::new(4, 0), Connectivity::Orthogonal, walkable,
+        );
+
+        assert!(path.complete);
+        assert_eq!(path.points.first(), Some(&Point2::new(0, 0)));
+        assert_eq!(path.points.last(), Some(&Point2::new(4, 0)));
+        assert_eq!(path.points.len(), 13);
+        assert_eq!(path.cost, 12.0);
+        assert!(path.points.contains(&Point2::new(2, 4)));
+    }
+
+    #[test]
+    fn astar_returns_partial_path_when_unreachable() {
+        let mut voxels: Voxels<bool> = Grid::empty(Vector3::new(4, 4, 4));
+        voxels.fill(Range::new_dim3(2, 0, 0, 2, 3, 3), true);
+        let walkable = |_, to| if *voxels.get(to).unwrap() { None } else { Some(1.0) };
+
+        let path = astar(
+            &voxels, Point3::new(0, 0, 0), Point3::new(3, 0, 0), Connectivity::Orthogonal,
+            walkable,
+        );
+
+        assert!(!path.complete);
+        assert_eq!(path.points.last(), Some(&Point3::new(1, 0, 0)));
+    }
+
+    #[test]
+    fn dijkstra_flows_to_nearest_goal() {
+        let tiles = walled_tiles();
+        let walkable = |from, to| {
+            if *tiles.get(from).unwrap() || *tiles.get(to).unwrap() { None } else { Some(1.0) }
+        };
+
+        let goals = [Point2::new(4, 0), Point2::new(0, 4)];
+        let field = dijkstra(&tiles, &goals, Connectivity::Orthogonal, walkable);
+
+        assert_eq!(field.cost(Point2::new(0, 0)), Some(4.0));
+        assert_eq!(field.cost(Point2::new(4, 4)), Some(4.0));
+        assert_eq!(field.cost(Point2::new(2, 0)), None);
+        assert_eq!(field.path_from(Point2::new(4, 2)).last(), Some(&Point2::new(4, 0)));
+    }
+}
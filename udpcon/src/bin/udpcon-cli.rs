@@ -0,0 +1,284 @@
+//! Command-line client and server for poking at udpcon endpoints.
+//!
+//! ```text
+//! udpcon-cli listen <bind address> <protocol>
+//! udpcon-cli connect <target address> <protocol> [bind address]
+//! ```
+//!
+//! Once running, commands are read from stdin, see `help` for a list.
+
+extern crate udpcon;
+
+use {
+    std::{
+        env,
+        io::{self, BufRead},
+        net::{SocketAddr},
+        process,
+        sync::mpsc::{self, Receiver, TryRecvError},
+        thread,
+        time::{Duration},
+    },
+
+    udpcon::{Peer, Reliability, Event},
+};
+
+const USAGE: &str = "\
+Usage:
+    udpcon-cli listen <bind address> <protocol>
+    udpcon-cli connect <target address> <protocol> [bind address]";
+
+const HELP: &str = "\
+Commands:
+    send <unreliable|sequenced> <text|hex> <payload>
+        Sends a payload to the default target.
+    sendto <address> <unreliable|sequenced> <text|hex> <payload>
+        Sends a payload to a specific address.
+    connect <address>
+        Connects to an address and makes it the default target.
+    stats
+        Prints statistics for all connections.
+    help
+        Prints this message.
+    quit
+        Stops the peer and exits.";
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    let (bind_address, connect_address, protocol) = match parse_args(&args) {
+        Ok(value) => value,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            process::exit(1);
+        },
+    };
+
+    // The peer needs the protocol for its entire lifetime, which is the lifetime of the program
+    let protocol: &'static str = Box::leak(protocol.into_boxed_str());
+    let mut peer = Peer::start(bind_address, protocol);
+    let mut target = None;
+
+    if let Some(address) = connect_address {
+        peer.connect(address);
+        target = Some(address);
+        println!("Connecting to {} with protocol \"{}\"", address, protocol);
+    } else {
+        println!("Listening on {} with protocol \"{}\"", bind_address.unwrap(), protocol);
+    }
+    println!("Type \"help\" for a list of commands");
+
+    let commands = spawn_stdin_reader();
+    let mut events = Vec::new();
+    loop {
+        // Handle everything the user typed since the last update
+        loop {
+            let line = match commands.try_recv() {
+                Ok(line) => line,
+                Err(TryRecvError::Empty) => break,
+                Err(TryRecvError::Disconnected) => {
+                    peer.stop();
+                    return
+                },
+            };
+
+            match handle_command(&line, &mut peer, &mut target) {
+                Ok(true) => {},
+                Ok(false) => {
+                    peer.stop();
+                    return
+                },
+                Err(message) => println!("Error: {}", message),
+            }
+        }
+
+        peer.update(&mut events);
+        for event in events.drain(..) {
+            print_event(&event);
+
+            // If we're listening, reply to whoever connected last by default
+            if let Event::NewPeer { address } = event {
+                if target.is_none() {
+                    target = Some(address);
+                }
+            }
+        }
+
+        thread::sleep(Duration::from_millis(10));
+    }
+}
+
+type Args = (Option<SocketAddr>, Option<SocketAddr>, String);
+
+fn parse_args(args: &[String]) -> Result<Args, String> {
+    match args.first().map(|s| s.as_str()) {
+        Some("listen") => {
+            if args.len() != 3 {
+                return Err("Expected a bind address and a protocol".into())
+            }
+
+            let bind = parse_address(&args[1])?;
+            Ok((Some(bind), None, args[2].clone()))
+        },
+        Some("connect") => {
+            if args.len() != 3 && args.len() != 4 {
+                return Err("Expected a target address, a protocol and optionally a bind address"
+                    .into())
+            }
+
+            let target = parse_address(&args[1])?;
+            let bind = match args.get(3) {
+                Some(bind) => Some(parse_address(bind)?),
+                None => None,
+            };
+            Ok((bind, Some(target), args[2].clone()))
+        },
+        Some(mode) => Err(format!("Unknown mode \"{}\"", mode)),
+        None => Err("Expected a mode".into()),
+    }
+}
+
+fn spawn_stdin_reader() -> Receiver<String> {
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let stdin = io::stdin();
+        for line in stdin.lock().lines() {
+            let line = match line {
+                Ok(line) => line,
+                Err(_) => break,
+            };
+
+            if sender.send(line).is_err() {
+                break
+            }
+        }
+    });
+
+    receiver
+}
+
+/// Handles a single line of input, returns false if the program should exit.
+fn handle_command(
+    line: &str, peer: &mut Peer, target: &mut Option<SocketAddr>,
+) -> Result<bool, String> {
+    let line = line.trim();
+    let (command, rest) = split_word(line);
+
+    match command {
+        "" => {},
+        "send" => {
+            let address = target.ok_or("No default target, use sendto or connect")?;
+            send(peer, address, rest)?;
+        },
+        "sendto" => {
+            let (address, rest) = split_word(rest);
+            let address = parse_address(address)?;
+            send(peer, address, rest)?;
+        },
+        "connect" => {
+            let address = parse_address(rest)?;
+            peer.connect(address);
+            *target = Some(address);
+            println!("Connecting to {}", address);
+        },
+        "stats" => print_stats(peer),
+        "help" => println!("{}", HELP),
+        "quit" | "exit" => return Ok(false),
+        _ => return Err(format!("Unknown command \"{}\", see \"help\"", command)),
+    }
+
+    Ok(true)
+}
+
+fn send(peer: &mut Peer, address: SocketAddr, arguments: &str) -> Result<(), String> {
+    let (reliability, rest) = split_word(arguments);
+    let (format, payload) = split_word(rest);
+
+    let reliability = match reliability {
+        "unreliable" | "u" => Reliability::Unreliable,
+        "sequenced" | "s" => Reliability::Sequenced,
+        _ => return Err(format!("Unknown reliability \"{}\"", reliability)),
+    };
+
+    let data = match format {
+        "text" | "t" => payload.as_bytes().to_vec(),
+        "hex" | "h" => parse_hex(payload)?,
+        _ => return Err(format!("Unknown payload format \"{}\"", format)),
+    };
+
+    let length = data.len();
+    peer.send(address, data, reliability)
+        .map_err(|e| format!("Failed to send: {:?}", e))?;
+    println!("Sent {} bytes to {}", length, address);
+
+    Ok(())
+}
+
+fn print_event(event: &Event) {
+    match *event {
+        Event::NewPeer { address } =>
+            println!("[new peer] {}", address),
+        Event::PeerTimedOut { address } =>
+            println!("[timed out] {}", address),
+        Event::Message { source, ref data } =>
+            println!("[message] {} ({} bytes): {}", source, data.len(), format_payload(data)),
+    }
+}
+
+fn print_stats(peer: &Peer) {
+    let connections = peer.connections();
+    if connections.is_empty() {
+        println!("No connections");
+        return
+    }
+
+    for (address, stats) in connections {
+        println!(
+            "{}: sent {} packets ({} bytes), received {} packets ({} bytes), \
+             last received {}ms ago",
+            address,
+            stats.packets_sent, stats.bytes_sent,
+            stats.packets_received, stats.bytes_received,
+            stats.since_last_received.as_millis(),
+        );
+    }
+}
+
+/// Shows the payload as text if it's printable, or as hex otherwise.
+fn format_payload(data: &[u8]) -> String {
+    match ::std::str::from_utf8(data) {
+        Ok(text) if !text.chars().any(|c| c.is_control()) => format!("\"{}\"", text),
+        _ => data.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" "),
+    }
+}
+
+fn parse_hex(value: &str) -> Result<Vec<u8>, String> {
+    // Allow bytes to be separated by whitespace for readability
+    let digits: String = value.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.is_ascii() {
+        return Err("Hex payload contains invalid characters".into())
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err("Hex payload has an odd amount of digits".into())
+    }
+
+    (0..digits.len()).step_by(2)
+        .map(|i| {
+            u8::from_str_radix(&digits[i..i+2], 16)
+                .map_err(|_| format!("Invalid hex byte \"{}\"", &digits[i..i+2]))
+        })
+        .collect()
+}
+
+fn parse_address(value: &str) -> Result<SocketAddr, String> {
+    value.parse().map_err(|_| format!("Invalid address \"{}\"", value))
+}
+
+fn split_word(value: &str) -> (&str, &str) {
+    let value = value.trim_start();
+    match value.find(char::is_whitespace) {
+        Some(index) => (&value[..index], value[index..].trim_start()),
+        None => (value, ""),
+    }
+}
@@ -5,11 +5,13 @@ extern crate num_traits;
 #[macro_use] extern crate num_derive;
 
 mod header;
+mod lockstep;
 mod peer;
 mod worker;
 
 pub use {
-    peer::{Peer, Reliability, Event},
+    lockstep::{LockstepSession, LockstepEvent, Player},
+    peer::{Peer, Reliability, Event, ConnectionStats},
 };
 
 #[derive(Debug)]
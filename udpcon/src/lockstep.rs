@@ -0,0 +1,628 @@
+use {
+    std::{
+        collections::{BTreeMap},
+        io::{Cursor, Read},
+        net::{SocketAddr},
+        time::{Instant, Duration},
+    },
+
+    byteorder::{WriteBytesExt, ReadBytesExt, LittleEndian},
+
+    header::{Header},
+    peer::{Peer, Reliability, Event},
+    Error, MTU_ESTIMATE,
+};
+
+/// How many unacknowledged inputs are resent at most in a single packet.
+const MAX_REDUNDANT_INPUTS: usize = 32;
+
+/// How many of the most recent local checksums are resent in every packet.
+const REDUNDANT_CHECKSUMS: usize = 4;
+
+/// How many local checksums are kept around to compare against late remote checksums.
+const KEPT_CHECKSUMS: usize = 64;
+
+/// How far past the current tick remote inputs are accepted. Inputs further ahead are dropped,
+/// the remote keeps resending them until they're acknowledged.
+const MAX_TICKS_AHEAD: u32 = 256;
+
+/// Space available for lockstep data in a single packet, after the peer's header.
+const MAX_PACKET_DATA: usize = MTU_ESTIMATE - Header::START_OFFSET;
+
+/// A deterministic lockstep session on top of a `Peer`.
+/// Every player submits one input per tick, and the session only advances to the next tick once
+/// the inputs of all players for it have arrived. Inputs are resent redundantly in every packet
+/// until the receiver acknowledges them, so no reliability is needed from the underlying `Peer`.
+pub struct LockstepSession {
+    peer: Peer,
+    players: Vec<Player>,
+
+    /// The next tick that will be advanced to.
+    current_tick: u32,
+    /// The tick the next submitted local input is for.
+    local_tick: u32,
+    local_inputs: BTreeMap<u32, Vec<u8>>,
+    local_checksums: BTreeMap<u32, u32>,
+    stalled_tick: Option<u32>,
+
+    remotes: Vec<RemoteState>,
+    resend_interval: Duration,
+}
+
+impl LockstepSession {
+    /// Starts a new lockstep session. `players` decides the order in which inputs are given back
+    /// when advancing, this order has to be the same on every peer in the session.
+    pub fn new(mut peer: Peer, players: Vec<Player>) -> Self {
+        let local_amount = players.iter().filter(|p| **p == Player::Local).count();
+        assert_eq!(local_amount, 1, "Lockstep session needs exactly one local player");
+
+        let mut remotes = Vec::new();
+        for player in &players {
+            if let Player::Remote(address) = *player {
+                peer.connect(address);
+                remotes.push(RemoteState::new(address));
+            }
+        }
+
+        LockstepSession {
+            peer,
+            players,
+
+            current_tick: 0,
+            local_tick: 0,
+            local_inputs: BTreeMap::new(),
+            local_checksums: BTreeMap::new(),
+            stalled_tick: None,
+
+            remotes,
+            resend_interval: Duration::from_millis(50),
+        }
+    }
+
+    /// Stops the session and returns the underlying peer.
+    pub fn into_peer(self) -> Peer {
+        self.peer
+    }
+
+    /// The next tick that will be advanced to.
+    pub fn current_tick(&self) -> u32 {
+        self.current_tick
+    }
+
+    /// The tick the next submitted local input will be for. Submitting inputs ahead of the
+    /// current tick adds input delay, which hides latency at the cost of responsiveness.
+    pub fn local_tick(&self) -> u32 {
+        self.local_tick
+    }
+
+    /// Sets how long to wait before resending unacknowledged inputs when no new input has been
+    /// submitted.
+    pub fn set_resend_interval(&mut self, interval: Duration) {
+        self.resend_interval = interval;
+    }
+
+    /// Submits the local input for the next local tick, and sends it to all remote players.
+    pub fn submit_input(&mut self, input: Vec<u8>) -> Result<u32, Error> {
+        // The input has to fit in a packet together with the rest of the lockstep data, including
+        // the most checksums a packet can carry, or it could never be sent
+        let max_checksums = REDUNDANT_CHECKSUMS * CHECKSUM_SIZE;
+        if input.len() + PACKET_OVERHEAD + INPUT_OVERHEAD + max_checksums > MAX_PACKET_DATA {
+            return Err(Error::DataTooLarge)
+        }
+
+        let tick = self.local_tick;
+        self.local_inputs.insert(tick, input);
+        self.local_tick += 1;
+
+        self.send_all(Instant::now());
+
+        Ok(tick)
+    }
+
+    /// Submits a checksum of the local simulation state after a tick. Remote players that submit
+    /// a checksum for the same tick will compare it, raising a `Desync` event if they don't match.
+    /// Submitting checksums is optional, and they are only exchanged on a best-effort basis.
+    pub fn submit_checksum(&mut self, tick: u32, checksum: u32, events: &mut Vec<LockstepEvent>) {
+        self.local_checksums.insert(tick, checksum);
+        while self.local_checksums.len() > KEPT_CHECKSUMS {
+            let oldest = *self.local_checksums.keys().next().unwrap();
+            self.local_checksums.remove(&oldest);
+        }
+
+        // Compare against anything the remotes sent us before we got to this tick
+        for remote in &mut self.remotes {
+            if let Some(remote_checksum) = remote.checksums.remove(&tick) {
+                if remote_checksum != checksum {
+                    events.push(LockstepEvent::Desync { tick, address: remote.address });
+                }
+            }
+        }
+    }
+
+    /// Receives remote inputs, resends unacknowledged inputs, and advances ticks for which all
+    /// inputs are present. Events from the underlying `Peer` that aren't lockstep data are passed
+    /// through as `LockstepEvent::Peer`.
+    pub fn update(&mut self, events: &mut Vec<LockstepEvent>) {
+        let now = Instant::now();
+
+        let mut peer_events = Vec::new();
+        self.peer.update(&mut peer_events);
+        for event in peer_events {
+            match event {
+                Event::Message { source, data } => {
+                    if let Some(index) = self.remotes.iter().position(|r| r.address == source) {
+                        // Garbage from a player is dropped, just like the peer drops garbage
+                        if let Some(packet) = LockstepPacket::decode(&data) {
+                            self.handle_packet(index, packet, events);
+                        }
+                    } else {
+                        events.push(LockstepEvent::Peer(Event::Message { source, data }));
+                    }
+                },
+                event => events.push(LockstepEvent::Peer(event)),
+            }
+        }
+
+        self.advance(events);
+
+        // Resend anything that hasn't been acknowledged yet, this also makes sure remotes find out
+        // what we've received from them
+        let resend_interval = self.resend_interval;
+        let needs_send: Vec<usize> = self.remotes.iter().enumerate()
+            .filter(|&(_, r)| now.duration_since(r.last_sent) >= resend_interval)
+            .map(|(i, _)| i)
+            .collect();
+        for index in needs_send {
+            self.send_to(index, now);
+        }
+    }
+
+    fn handle_packet(
+        &mut self, index: usize, packet: LockstepPacket, events: &mut Vec<LockstepEvent>,
+    ) {
+        let current_tick = self.current_tick;
+        let remote = &mut self.remotes[index];
+
+        if packet.ack > remote.acked_tick {
+            remote.acked_tick = packet.ack;
+        }
+
+        for (tick, input) in packet.inputs {
+            // Anything before what we've already got in full is a redundant resend, and anything
+            // too far ahead could only be kept around without limit
+            let is_ahead = tick >= remote.received_tick && tick >= current_tick;
+            if is_ahead && tick - current_tick < MAX_TICKS_AHEAD {
+                remote.inputs.entry(tick).or_insert(input);
+            }
+        }
+        while remote.inputs.contains_key(&remote.received_tick) {
+            remote.received_tick += 1;
+        }
+
+        for (tick, checksum) in packet.checksums {
+            if tick < remote.checked_tick { continue }
+
+            if let Some(local_checksum) = self.local_checksums.get(&tick) {
+                if *local_checksum != checksum {
+                    events.push(LockstepEvent::Desync { tick, address: remote.address });
+                }
+                remote.checksums.remove(&tick);
+            } else {
+                remote.checksums.insert(tick, checksum);
+            }
+            remote.checked_tick = tick + 1;
+        }
+
+        // Checksums we never submit a match for would otherwise pile up forever
+        while remote.checksums.len() > KEPT_CHECKSUMS {
+            let oldest = *remote.checksums.keys().next().unwrap();
+            remote.checksums.remove(&oldest);
+        }
+
+        self.prune_local_inputs();
+    }
+
+    fn advance(&mut self, events: &mut Vec<LockstepEvent>) {
+        loop {
+            let tick = self.current_tick;
+
+            // If we don't have our own input yet, the game simply hasn't gotten to this tick
+            if !self.local_inputs.contains_key(&tick) {
+                return
+            }
+
+            let waiting_on: Vec<SocketAddr> = self.remotes.iter()
+                .filter(|r| !r.inputs.contains_key(&tick))
+                .map(|r| r.address)
+                .collect();
+            if !waiting_on.is_empty() {
+                // Only report a stall once per tick, it will keep being stalled until inputs come
+                if self.stalled_tick != Some(tick) {
+                    self.stalled_tick = Some(tick);
+                    events.push(LockstepEvent::Stall { tick, waiting_on });
+                }
+                return
+            }
+
+            // We've got everything, gather the inputs in player order
+            let mut inputs = Vec::with_capacity(self.players.len());
+            for player in &self.players {
+                let input = match *player {
+                    Player::Local => self.local_inputs[&tick].clone(),
+                    Player::Remote(address) => {
+                        let remote = self.remotes.iter_mut()
+                            .find(|r| r.address == address).unwrap();
+                        remote.inputs.remove(&tick).unwrap()
+                    },
+                };
+                inputs.push(input);
+            }
+
+            self.current_tick += 1;
+            self.stalled_tick = None;
+            self.prune_local_inputs();
+            events.push(LockstepEvent::Advance { tick, inputs });
+        }
+    }
+
+    fn prune_local_inputs(&mut self) {
+        // We need to keep inputs until every remote has them and we've used them ourselves
+        let acked = self.remotes.iter().map(|r| r.acked_tick).min().unwrap_or(self.local_tick);
+        let keep_from = acked.min(self.current_tick);
+
+        while let Some(&oldest) = self.local_inputs.keys().next() {
+            if oldest >= keep_from { break }
+            self.local_inputs.remove(&oldest);
+        }
+    }
+
+    fn send_all(&mut self, now: Instant) {
+        for index in 0..self.remotes.len() {
+            self.send_to(index, now);
+        }
+    }
+
+    fn send_to(&mut self, index: usize, now: Instant) {
+        let remote = &mut self.remotes[index];
+        remote.last_sent = now;
+
+        // Checksums are sent oldest first, the receiver expects them in order
+        let mut checksums: Vec<(u32, u32)> = self.local_checksums.iter().rev()
+            .take(REDUNDANT_CHECKSUMS)
+            .map(|(tick, checksum)| (*tick, *checksum))
+            .collect();
+        checksums.reverse();
+
+        let mut packet = LockstepPacket {
+            ack: remote.received_tick,
+            inputs: Vec::new(),
+            checksums,
+        };
+
+        // Oldest inputs go first, the remote can't advance without those
+        let mut size = packet.encoded_len();
+        for (tick, input) in self.local_inputs.range(remote.acked_tick..) {
+            let input_size = INPUT_OVERHEAD + input.len();
+            if packet.inputs.len() >= MAX_REDUNDANT_INPUTS || size + input_size > MAX_PACKET_DATA {
+                break
+            }
+
+            size += input_size;
+            packet.inputs.push((*tick, input.clone()));
+        }
+
+        // Packets are sized to fit so this can't fail
+        self.peer.send(remote.address, packet.encode(), Reliability::Unreliable).unwrap();
+    }
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Player {
+    Local,
+    Remote(SocketAddr),
+}
+
+#[derive(Debug)]
+pub enum LockstepEvent {
+    /// All inputs for a tick are present, `inputs` are in the order of the session's players.
+    Advance { tick: u32, inputs: Vec<Vec<u8>> },
+    /// The session can't advance because inputs for a tick are missing from some players.
+    Stall { tick: u32, waiting_on: Vec<SocketAddr> },
+    /// A remote player submitted a different checksum for a tick than we did.
+    Desync { tick: u32, address: SocketAddr },
+    /// An event from the underlying peer that isn't lockstep data.
+    Peer(Event),
+}
+
+struct RemoteState {
+    address: SocketAddr,
+    last_sent: Instant,
+
+    /// The first tick this remote hasn't received our input for.
+    acked_tick: u32,
+    /// The first tick we haven't received this remote's input for.
+    received_tick: u32,
+    inputs: BTreeMap<u32, Vec<u8>>,
+
+    /// The first tick we haven't received a checksum for yet.
+    checked_tick: u32,
+    checksums: BTreeMap<u32, u32>,
+}
+
+impl RemoteState {
+    fn new(address: SocketAddr) -> Self {
+        RemoteState {
+            address,
+            last_sent: Instant::now() - Duration::new(10, 0),
+
+            acked_tick: 0,
+            received_tick: 0,
+            inputs: BTreeMap::new(),
+
+            checked_tick: 0,
+            checksums: BTreeMap::new(),
+        }
+    }
+}
+
+/// Ack, input count, and checksum count.
+const PACKET_OVERHEAD: usize = 4 + 1 + 1;
+/// Tick and input length.
+const INPUT_OVERHEAD: usize = 4 + 2;
+/// Tick and checksum.
+const CHECKSUM_SIZE: usize = 4 + 4;
+
+#[derive(PartialEq, Debug)]
+struct LockstepPacket {
+    ack: u32,
+    inputs: Vec<(u32, Vec<u8>)>,
+    checksums: Vec<(u32, u32)>,
+}
+
+impl LockstepPacket {
+    fn encoded_len(&self) -> usize {
+        PACKET_OVERHEAD +
+            self.inputs.iter().map(|(_, i)| INPUT_OVERHEAD + i.len()).sum::<usize>() +
+            self.checksums.len() * CHECKSUM_SIZE
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut data = Vec::with_capacity(self.encoded_len());
+
+        data.write_u32::<LittleEndian>(self.ack).unwrap();
+
+        data.push(self.inputs.len() as u8);
+        for &(tick, ref input) in &self.inputs {
+            data.write_u32::<LittleEndian>(tick).unwrap();
+            data.write_u16::<LittleEndian>(input.len() as u16).unwrap();
+            data.extend_from_slice(input);
+        }
+
+        data.push(self.checksums.len() as u8);
+        for &(tick, checksum) in &self.checksums {
+            data.write_u32::<LittleEndian>(tick).unwrap();
+            data.write_u32::<LittleEndian>(checksum).unwrap();
+        }
+
+        data
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        let mut cursor = Cursor::new(data);
+
+        let ack = cursor.read_u32::<LittleEndian>().ok()?;
+
+        let input_amount = cursor.read_u8().ok()?;
+        let mut inputs = Vec::with_capacity(input_amount as usize);
+        for _ in 0..input_amount {
+            let tick = cursor.read_u32::<LittleEndian>().ok()?;
+            let length = cursor.read_u16::<LittleEndian>().ok()?;
+            let mut input = vec![0; length as usize];
+            cursor.read_exact(&mut input).ok()?;
+            inputs.push((tick, input));
+        }
+
+        let checksum_amount = cursor.read_u8().ok()?;
+        let mut checksums = Vec::with_capacity(checksum_amount as usize);
+        for _ in 0..checksum_amount {
+            let tick = cursor.read_u32::<LittleEndian>().ok()?;
+            let checksum = cursor.read_u32::<LittleEndian>().ok()?;
+            checksums.push((tick, checksum));
+        }
+
+        Some(LockstepPacket {
+            ack,
+            inputs,
+            checksums,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::{thread};
+
+    const MAX_INPUT: usize =
+        MAX_PACKET_DATA - PACKET_OVERHEAD - INPUT_OVERHEAD - REDUNDANT_CHECKSUMS * CHECKSUM_SIZE;
+
+    fn address(port: u16) -> SocketAddr {
+        format!("127.0.0.1:{}", port).parse().unwrap()
+    }
+
+    fn session(port: u16, remote_port: u16) -> LockstepSession {
+        let peer = Peer::start(Some(address(port)), "udpcon-lockstep-test");
+        let mut session = LockstepSession::new(
+            peer, vec![Player::Local, Player::Remote(address(remote_port))],
+        );
+        session.set_resend_interval(Duration::from_millis(5));
+        session
+    }
+
+    /// Updates both sessions until `done` returns true for the events gathered so far, or panics
+    /// after a timeout.
+    fn update_until<F: Fn(&[LockstepEvent], &[LockstepEvent]) -> bool>(
+        a: &mut LockstepSession, a_events: &mut Vec<LockstepEvent>,
+        b: &mut LockstepSession, b_events: &mut Vec<LockstepEvent>,
+        done: F,
+    ) {
+        let start = Instant::now();
+        while !done(a_events, b_events) {
+            assert!(start.elapsed() < Duration::new(5, 0), "Timed out waiting for sessions");
+            a.update(a_events);
+            b.update(b_events);
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    fn advanced(events: &[LockstepEvent], tick: u32) -> Option<&Vec<Vec<u8>>> {
+        events.iter()
+            .filter_map(|e| match *e {
+                LockstepEvent::Advance { tick: t, ref inputs } if t == tick => Some(inputs),
+                _ => None,
+            })
+            .next()
+    }
+
+    #[test]
+    fn packet_serialization_works_two_ways() {
+        let packet = LockstepPacket {
+            ack: 12,
+            inputs: vec![(12, vec![1, 2, 3]), (13, vec![]), (14, vec![55; 40])],
+            checksums: vec![(10, 0xDEADBEEF), (11, 5)],
+        };
+
+        let data = packet.encode();
+        assert_eq!(data.len(), packet.encoded_len());
+
+        let new_packet = LockstepPacket::decode(&data);
+        assert_eq!(Some(packet), new_packet);
+    }
+
+    #[test]
+    fn packet_decoding_rejects_truncated_data() {
+        let packet = LockstepPacket {
+            ack: 3,
+            inputs: vec![(3, vec![1, 2, 3, 4])],
+            checksums: vec![],
+        };
+
+        let data = packet.encode();
+        assert_eq!(None, LockstepPacket::decode(&data[..data.len() - 3]));
+    }
+
+    #[test]
+    fn maximum_size_inputs_are_delivered() {
+        let mut a = session(41210, 41211);
+        let mut b = session(41211, 41210);
+        let (mut a_events, mut b_events) = (Vec::new(), Vec::new());
+
+        // Fill up the packets with as many checksums as they can carry
+        for tick in 0..REDUNDANT_CHECKSUMS as u32 {
+            a.submit_checksum(tick + 100, tick, &mut a_events);
+        }
+        assert!(a.submit_input(vec![0; MAX_INPUT + 1]).is_err());
+        a.submit_input(vec![7; MAX_INPUT]).unwrap();
+        b.submit_input(vec![1]).unwrap();
+
+        update_until(
+            &mut a, &mut a_events, &mut b, &mut b_events,
+            |_, b| advanced(b, 0).is_some(),
+        );
+        assert_eq!(advanced(&b_events, 0).unwrap(), &vec![vec![1], vec![7; MAX_INPUT]]);
+    }
+
+    #[test]
+    fn sessions_advance_and_stall_on_missing_inputs() {
+        let mut a = session(41220, 41221);
+        let mut b = session(41221, 41220);
+        let (mut a_events, mut b_events) = (Vec::new(), Vec::new());
+
+        a.submit_input(vec![1]).unwrap();
+        a.update(&mut a_events);
+        match a_events[..] {
+            [LockstepEvent::Stall { tick: 0, ref waiting_on }] =>
+                assert_eq!(waiting_on, &vec![address(41221)]),
+            ref events => panic!("Expected a stall, got {:?}", events),
+        }
+
+        b.submit_input(vec![2]).unwrap();
+        update_until(
+            &mut a, &mut a_events, &mut b, &mut b_events,
+            |a, b| advanced(a, 0).is_some() && advanced(b, 0).is_some(),
+        );
+        assert_eq!(advanced(&a_events, 0).unwrap(), &vec![vec![1], vec![2]]);
+        assert_eq!(advanced(&b_events, 0).unwrap(), &vec![vec![2], vec![1]]);
+        assert_eq!((a.current_tick(), b.current_tick()), (1, 1));
+    }
+
+    #[test]
+    fn lost_inputs_are_resent() {
+        // Nothing is listening on the remote's port yet, so this input is lost
+        let mut a = session(41230, 41231);
+        let mut a_events = Vec::new();
+        a.submit_input(vec![1]).unwrap();
+        a.submit_input(vec![2]).unwrap();
+        a.update(&mut a_events);
+
+        let mut b = session(41231, 41230);
+        let mut b_events = Vec::new();
+        b.submit_input(vec![3]).unwrap();
+        b.submit_input(vec![4]).unwrap();
+        update_until(
+            &mut a, &mut a_events, &mut b, &mut b_events,
+            |a, b| advanced(a, 1).is_some() && advanced(b, 1).is_some(),
+        );
+        assert_eq!(advanced(&b_events, 0).unwrap(), &vec![vec![3], vec![1]]);
+        assert_eq!(advanced(&b_events, 1).unwrap(), &vec![vec![4], vec![2]]);
+    }
+
+    #[test]
+    fn mismatched_checksums_raise_desync() {
+        let mut a = session(41240, 41241);
+        let mut b = session(41241, 41240);
+        let (mut a_events, mut b_events) = (Vec::new(), Vec::new());
+
+        a.submit_checksum(0, 10, &mut a_events);
+        b.submit_checksum(0, 11, &mut b_events);
+        a.submit_input(vec![]).unwrap();
+        b.submit_input(vec![]).unwrap();
+
+        let desynced = |events: &[LockstepEvent]| events.iter()
+            .any(|e| matches!(*e, LockstepEvent::Desync { tick: 0, .. }));
+        update_until(
+            &mut a, &mut a_events, &mut b, &mut b_events,
+            |a, b| desynced(a) && desynced(b),
+        );
+    }
+
+    #[test]
+    fn unmatched_remote_checksums_are_limited() {
+        let mut a = session(41250, 41251);
+        let mut events = Vec::new();
+
+        for tick in 0..KEPT_CHECKSUMS as u32 * 2 {
+            let packet = LockstepPacket { ack: 0, inputs: vec![], checksums: vec![(tick, 1)] };
+            a.handle_packet(0, packet, &mut events);
+        }
+
+        let checksums = &a.remotes[0].checksums;
+        assert_eq!(checksums.len(), KEPT_CHECKSUMS);
+        assert_eq!(checksums.keys().next(), Some(&(KEPT_CHECKSUMS as u32)));
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn inputs_too_far_ahead_are_dropped() {
+        let mut a = session(41260, 41261);
+        let mut events = Vec::new();
+
+        let inputs = vec![(0, vec![1]), (MAX_TICKS_AHEAD, vec![2]), (u32::MAX, vec![3])];
+        a.handle_packet(0, LockstepPacket { ack: 0, inputs, checksums: vec![] }, &mut events);
+
+        let ticks: Vec<u32> = a.remotes[0].inputs.keys().cloned().collect();
+        assert_eq!(ticks, vec![0]);
+        assert_eq!(a.remotes[0].received_tick, 1);
+    }
+}
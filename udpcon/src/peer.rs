@@ -82,6 +82,11 @@ impl Peer {
             if let Some((header, data)) = Header::extract(data, self.protocol_id) {
                 // Update when the last time we got a packet was
                 self.update_last_packet(source, now, events);
+                {
+                    let stats = &mut self.connections.get_mut(&source).unwrap().stats;
+                    stats.packets_received += 1;
+                    stats.bytes_received += (data.len() + Header::START_OFFSET) as u64;
+                }
 
                 match header.class {
                     PacketClass::UnreliableMessage =>
@@ -119,6 +124,19 @@ impl Peer {
         self.send_heartbeats(now);
     }
 
+    /// Returns the addresses of all peers we currently have a connection with, together with
+    /// statistics about the traffic on those connections.
+    pub fn connections(&self) -> Vec<(SocketAddr, ConnectionStats)> {
+        let now = Instant::now();
+        self.connections.iter()
+            .map(|(address, connection)| {
+                let mut stats = connection.stats.clone();
+                stats.since_last_received = now.duration_since(connection.last_received);
+                (*address, stats)
+            })
+            .collect()
+    }
+
     fn update_last_packet(&mut self, source: SocketAddr, now: Instant, events: &mut Vec<Event>) {
         if self.connections.contains_key(&source) {
             // We currently have a connection with this peer, update the last time we saw it
@@ -129,6 +147,7 @@ impl Peer {
                 last_received: now,
                 last_sent: now - Duration::new(10, 0),
                 last_received_packet_number: 0,
+                stats: ConnectionStats::default(),
             });
             events.push(Event::NewPeer { address: source })
         }
@@ -181,6 +200,8 @@ impl Peer {
         // send empty heartbeats to keep the connection alive
         if let Some(connection) = self.connections.get_mut(&target) {
             connection.last_sent = Instant::now();
+            connection.stats.packets_sent += 1;
+            connection.stats.bytes_sent += data.len() as u64;
         }
 
         self.worker.send(target, data);
@@ -212,10 +233,24 @@ pub enum Event {
     Message { source: SocketAddr, data: Vec<u8> },
 }
 
+#[derive(Clone, Default, Debug)]
+pub struct ConnectionStats {
+    /// Packets sent, including heartbeats.
+    pub packets_sent: u64,
+    /// Packets received, including heartbeats.
+    pub packets_received: u64,
+    /// Bytes sent, including headers.
+    pub bytes_sent: u64,
+    /// Bytes received, including headers.
+    pub bytes_received: u64,
+    pub since_last_received: Duration,
+}
+
 struct PeerConnection {
     last_received: Instant,
     last_sent: Instant,
     last_received_packet_number: u16,
+    stats: ConnectionStats,
 }
 
 fn sequence_greater_than(previous: u16, next: u16) -> bool {
@@ -52,9 +52,15 @@ impl PacketWorker {
     }
 
     pub fn stop(&mut self) {
+        // Stopping an already stopped worker is fine, this happens when a stopped peer is dropped
+        let worker_thread = match self.worker_thread.take() {
+            Some(worker_thread) => worker_thread,
+            None => return,
+        };
+
         self.outgoing.send(WorkerMessage::Stop).unwrap();
         self.outgoing_set.set_readiness(Ready::readable()).unwrap();
-        self.worker_thread.take().unwrap().join().unwrap();
+        worker_thread.join().unwrap();
     }
 
     pub fn try_recv(&self) -> Option<(SocketAddr, Vec<u8>)> {